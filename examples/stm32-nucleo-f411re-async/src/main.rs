@@ -76,18 +76,23 @@ async fn sensor_read(mut sensor_int: ExtiInput<'static>) {
         {
             let mut tsl2591 = TSL2591_MTX.lock().await;
             let tsl2591 = unwrap!(tsl2591.as_mut());
-            tsl2591
-                .clear_interrupt()
-                .await
-                .expect("Unable to clear interrupt");
+            // Only clear the interrupt source that actually fired
+            let status = tsl2591.get_status().await.expect("Failed to read status");
+            if status.aint {
+                tsl2591
+                    .special_function(SpecialFunction::ClearAlsInterrupt)
+                    .await
+                    .expect("Unable to clear interrupt");
+            }
+            if status.npintr {
+                tsl2591
+                    .clear_np_interrupt()
+                    .await
+                    .expect("Unable to clear no-persist interrupt");
+            }
 
             let lux = tsl2591.get_lux(true).await.expect("Failed to retrieve lux");
-            core::write!(
-                &mut s,
-                "Lux: {}\r\n",
-                lux.integer as f32 + lux.fractional as f32 / 1_000_000f32
-            )
-            .unwrap();
+            core::write!(&mut s, "Lux: {:.3}\r\n", lux).unwrap();
         }
         uart_write(s.as_str()).await;
     }
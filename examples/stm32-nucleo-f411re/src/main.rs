@@ -23,7 +23,7 @@ fn main() -> ! {
     let rcc = dp.RCC.constrain();
     let clocks = rcc.cfgr.freeze();
 
-    // Set delay for pause between sensor readings
+    // Set delay for pause between sensor readings and for waiting on integration
     let mut delay = cp.SYST.delay(&clocks);
 
     // Setup GPIO pins for use by I2C
@@ -59,12 +59,11 @@ fn main() -> ! {
     rprintln!("Sensor gain set to Med, integration time set to 200ms");
 
     /* Retrieve sensor readings every second and calculate lux.
-     * Must wait at least as long as the integration time between readings to ensure validity.
+     * measure_lux waits out the integration time itself, so every reading is fresh.
      */
     loop {
         delay.delay_ms(1000);
-        let lux: Lux = tsl2591.get_lux(true).expect("Failed to get lux");
-        let lux = lux.integer as f32 + lux.fractional as f32 / 1_000_000f32;
-        rprintln!("Lux: {}", lux);
+        let lux: Lux = tsl2591.measure_lux(&mut delay).expect("Failed to get lux");
+        rprintln!("Lux: {:.3}", lux);
     }
 }
@@ -0,0 +1,100 @@
+use crate::{Gain, Integration};
+
+/* Sensitivity ladder walked by auto-ranging, from least to most sensitive.
+ * Neighbouring rungs differ by up to 6.25x in sensitivity (gain * integration time), and
+ * stepping onto a 100ms rung also lowers full scale, so a reading just under the low end of
+ * the window can land above the high end one rung up.
+ */
+const LADDER: [(Gain, Integration); 9] = [
+    (Gain::Low, Integration::T100ms),
+    (Gain::Low, Integration::T400ms),
+    (Gain::Med, Integration::T100ms),
+    (Gain::Med, Integration::T400ms),
+    (Gain::High, Integration::T100ms),
+    (Gain::High, Integration::T400ms),
+    (Gain::Max, Integration::T100ms),
+    (Gain::Max, Integration::T200ms),
+    (Gain::Max, Integration::T600ms),
+];
+
+// Enough steps to walk from one end of the ladder to the other
+pub(crate) const MAX_STEPS: usize = LADDER.len();
+
+// Hysteresis window for auto-ranging, as percentages of the full-scale ADC count
+#[derive(Clone, Copy, Debug)]
+pub struct AutoRange {
+    // Step up one rung when the larger channel falls below this percentage of full scale
+    pub low_percent: u8,
+
+    // Step down one rung when the larger channel rises above this percentage of full scale
+    pub high_percent: u8,
+}
+
+impl Default for AutoRange {
+    fn default() -> Self {
+        AutoRange {
+            low_percent: 10,
+            high_percent: 80,
+        }
+    }
+}
+
+impl AutoRange {
+    fn sensitivity(gain: Gain, integration: Integration) -> u32 {
+        gain.multiplier() as u32 * integration.millis() as u32
+    }
+
+    /* Returns the gain and integration time to use for the next cycle, or None if the
+     * current reading is within the hysteresis window, stepping up would overshoot it, or the
+     * ladder is exhausted.
+     * Settings that aren't on the ladder are treated as the nearest rung below them.
+     */
+    pub(crate) fn next_setting(
+        &self,
+        gain: Gain,
+        integration: Integration,
+        counts: u16,
+        saturated: bool,
+    ) -> Option<(Gain, Integration)> {
+        let current = Self::sensitivity(gain, integration);
+        let rung = LADDER
+            .iter()
+            .rposition(|&(g, i)| Self::sensitivity(g, i) <= current)
+            .unwrap_or(0);
+
+        let full_scale = integration.max_count() as u32;
+        let counts = counts as u32;
+
+        let next = if saturated || counts * 100 > full_scale * self.high_percent as u32 {
+            if rung == 0 && LADDER[0] == (gain, integration) {
+                return None;
+            }
+
+            // Off-ladder settings step down onto their nearest rung first
+            if LADDER[rung] == (gain, integration) {
+                rung - 1
+            } else {
+                rung
+            }
+        } else if counts * 100 < full_scale * self.low_percent as u32 {
+            if rung + 1 >= LADDER.len() {
+                return None;
+            }
+
+            // Only step up if the reading would still be below the high end once there,
+            // otherwise the next cycle steps straight back down again
+            let (next_gain, next_integration) = LADDER[rung + 1];
+            let next_counts = counts as u64 * Self::sensitivity(next_gain, next_integration) as u64;
+            let next_full_scale = next_integration.max_count() as u64 * current as u64;
+            if next_counts * 100 >= next_full_scale * self.high_percent as u64 {
+                return None;
+            }
+
+            rung + 1
+        } else {
+            return None;
+        };
+
+        Some(LADDER[next])
+    }
+}
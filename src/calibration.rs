@@ -0,0 +1,122 @@
+use crate::Lux;
+
+/* Correction applied to the lux computed by the active lux model:
+ *   lux = model_lux * glass_attenuation / 1000 * slope / 1_000_000 + offset
+ * Fields are fixed-point so the correction stays integer-only like the rest of the driver.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Calibration {
+    // Glass attenuation factor in thousandths, e.g. 2500 behind glass that passes 40% of the light
+    pub glass_attenuation: u32,
+
+    // Slope of a linear fit against a reference meter, in millionths
+    pub slope: i64,
+
+    // Added after scaling, in micro-lux
+    pub offset: i64,
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Calibration {
+    // Open air, no correction
+    pub const IDENTITY: Calibration = Calibration {
+        glass_attenuation: 1000,
+        slope: 1_000_000,
+        offset: 0,
+    };
+
+    pub fn glass(glass_attenuation: u32) -> Self {
+        Calibration {
+            glass_attenuation,
+            ..Self::IDENTITY
+        }
+    }
+
+    /* Glass attenuation from one uncalibrated reading and a reference meter reading of the
+     * same light. Returns None unless both readings are positive, since a zero attenuation
+     * would read every later sample as darkness.
+     */
+    pub fn single_point(raw: Lux, reference: Lux) -> Option<Self> {
+        let (raw, reference) = (raw.micro_lux(), reference.micro_lux());
+        if raw <= 0 || reference <= 0 {
+            return None;
+        }
+
+        let glass_attenuation = reference as i128 * 1000 / raw as i128;
+        let glass_attenuation = u32::try_from(glass_attenuation).ok().filter(|&g| g > 0)?;
+        Some(Self::glass(glass_attenuation))
+    }
+
+    /* Linear fit through two (uncalibrated, reference) pairs, ideally taken far apart in
+     * brightness. Returns None if the pairs don't describe an increasing line.
+     */
+    pub fn two_point(low: (Lux, Lux), high: (Lux, Lux)) -> Option<Self> {
+        let (raw_low, ref_low) = (low.0.micro_lux() as i128, low.1.micro_lux() as i128);
+        let (raw_high, ref_high) = (high.0.micro_lux() as i128, high.1.micro_lux() as i128);
+        if raw_high == raw_low {
+            return None;
+        }
+
+        let slope = (ref_high - ref_low) * 1_000_000 / (raw_high - raw_low);
+        if slope <= 0 {
+            return None;
+        }
+        let offset = ref_low - raw_low * slope / 1_000_000;
+
+        Some(Calibration {
+            glass_attenuation: 1000,
+            slope: i64::try_from(slope).ok()?,
+            offset: i64::try_from(offset).ok()?,
+        })
+    }
+
+    pub fn apply(&self, lux: Lux) -> Lux {
+        Lux::from_micro_lux(self.apply_micro(lux.micro_lux()))
+    }
+
+    pub(crate) fn apply_micro(&self, micro_lux: i64) -> i64 {
+        let scaled =
+            micro_lux as i128 * self.glass_attenuation as i128 * self.slope as i128 / 1_000_000_000;
+        (scaled + self.offset as i128).clamp(i64::MIN as i128, i64::MAX as i128) as i64
+    }
+
+    // Maps calibrated micro-lux back to what the lux model would have to report
+    pub(crate) fn invert_micro(&self, micro_lux: i64) -> i64 {
+        let divisor = self.glass_attenuation as i128 * self.slope as i128;
+        if divisor <= 0 {
+            return i64::MAX;
+        }
+
+        let unscaled = (micro_lux as i128 - self.offset as i128) * 1_000_000_000 / divisor;
+        unscaled.clamp(i64::MIN as i128, i64::MAX as i128) as i64
+    }
+}
+
+#[cfg(feature = "float")]
+#[duplicate::duplicate_item(
+    float apply_float single_point_float two_point_float from_float;
+    [f32] [apply_f32] [single_point_f32] [two_point_f32] [from_f32];
+    [f64] [apply_f64] [single_point_f64] [two_point_f64] [from_f64];
+)]
+impl Calibration {
+    pub fn apply_float(&self, lux: float) -> float {
+        let scale = self.glass_attenuation as float / 1000.0 * self.slope as float / 1_000_000.0;
+        lux * scale + self.offset as float / 1_000_000.0
+    }
+
+    pub fn single_point_float(raw: float, reference: float) -> Option<Self> {
+        Self::single_point(Lux::from_float(raw), Lux::from_float(reference))
+    }
+
+    pub fn two_point_float(low: (float, float), high: (float, float)) -> Option<Self> {
+        Self::two_point(
+            (Lux::from_float(low.0), Lux::from_float(low.1)),
+            (Lux::from_float(high.0), Lux::from_float(high.1)),
+        )
+    }
+}
@@ -0,0 +1,154 @@
+use duplicate::duplicate_item;
+
+use crate::{Error, Lux, LuxModel, Tsl2591, Tsl2591Async};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FusionStrategy {
+    Mean,
+    Median,
+    Min,
+    Max,
+    // Mean weighted by each sensor's weight, e.g. the floor area it covers
+    Weighted,
+}
+
+// What happened to each sensor in the last sample
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SensorHealth<E> {
+    Ok(Lux),
+    // Too bright for the current gain and integration time, left out of the result
+    Saturated,
+    // Integration cycle hadn't completed yet
+    NotReady,
+    Failed(Error<E>),
+}
+
+impl<E> SensorHealth<E> {
+    pub fn lux(&self) -> Option<Lux> {
+        match self {
+            SensorHealth::Ok(lux) => Some(*lux),
+            _ => None,
+        }
+    }
+}
+
+impl<E> From<Result<Lux, Error<E>>> for SensorHealth<E> {
+    fn from(result: Result<Lux, Error<E>>) -> Self {
+        match result {
+            Ok(lux) => SensorHealth::Ok(lux),
+            Err(Error::AdcSaturated(_)) => SensorHealth::Saturated,
+            Err(Error::CycleIncomplete) => SensorHealth::NotReady,
+            Err(error) => SensorHealth::Failed(error),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FusedReading<E, const N: usize> {
+    // None if no sensor (or, for Weighted, no sensor with a non-zero weight) gave a reading
+    pub lux: Option<Lux>,
+
+    // Number of sensors that went into the result
+    pub used: usize,
+
+    pub health: [SensorHealth<E>; N],
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Fusion<const N: usize> {
+    pub strategy: FusionStrategy,
+
+    // Only used by FusionStrategy::Weighted
+    pub weights: [u16; N],
+}
+
+impl<const N: usize> Fusion<N> {
+    pub fn new(strategy: FusionStrategy) -> Self {
+        Fusion {
+            strategy,
+            weights: [1; N],
+        }
+    }
+
+    pub fn weighted(weights: [u16; N]) -> Self {
+        Fusion {
+            strategy: FusionStrategy::Weighted,
+            weights,
+        }
+    }
+
+    // Combines one reading per sensor, e.g. from Tsl2591Array::read_all
+    pub fn combine<E>(&self, readings: [Result<Lux, Error<E>>; N]) -> FusedReading<E, N> {
+        let health = readings.map(SensorHealth::from);
+
+        let mut values = [0i64; N];
+        let mut weights = [0i64; N];
+        let mut used = 0;
+        for (index, sensor) in health.iter().enumerate() {
+            if let Some(lux) = sensor.lux() {
+                if self.strategy == FusionStrategy::Weighted && self.weights[index] == 0 {
+                    continue;
+                }
+                values[used] = lux.micro_lux();
+                weights[used] = self.weights[index] as i64;
+                used += 1;
+            }
+        }
+        let (values, weights) = (&mut values[..used], &weights[..used]);
+
+        let micro_lux = if used == 0 {
+            None
+        } else {
+            Some(match self.strategy {
+                FusionStrategy::Mean => values.iter().sum::<i64>() / used as i64,
+                FusionStrategy::Median => {
+                    values.sort_unstable();
+                    if used % 2 == 1 {
+                        values[used / 2]
+                    } else {
+                        (values[used / 2 - 1] + values[used / 2]) / 2
+                    }
+                }
+                FusionStrategy::Min => *values.iter().min().unwrap_or(&0),
+                FusionStrategy::Max => *values.iter().max().unwrap_or(&0),
+                FusionStrategy::Weighted => {
+                    let total: i64 = weights.iter().sum();
+                    let sum: i128 = values
+                        .iter()
+                        .zip(weights)
+                        .map(|(&value, &weight)| value as i128 * weight as i128)
+                        .sum();
+                    (sum / total as i128) as i64
+                }
+            })
+        };
+
+        FusedReading {
+            lux: micro_lux.map(Lux::from_micro_lux),
+            used,
+            health,
+        }
+    }
+}
+
+#[duplicate_item(
+    _hal_ async add_await(code) sample _generics_ _sensor_;
+    [embedded_hal] [] [code] [sample] [I: embedded_hal::i2c::I2c, M: LuxModel] [Tsl2591<I, M>];
+    [embedded_hal_async] [async] [code.await] [sample_async]
+        [I: embedded_hal_async::i2c::I2c, P, M: LuxModel] [Tsl2591Async<I, P, M>];
+)]
+impl<const N: usize> Fusion<N> {
+    // Reads every sensor in turn, then combines whatever readings succeeded
+    pub async fn sample<_generics_>(
+        &self,
+        sensors: &mut [_sensor_; N],
+        check_complete: bool,
+    ) -> FusedReading<I::Error, N> {
+        let mut readings = core::array::from_fn(|_| Err(Error::CycleIncomplete));
+        for (reading, sensor) in readings.iter_mut().zip(sensors.iter_mut()) {
+            *reading = add_await([sensor.get_lux(check_complete)]);
+        }
+
+        self.combine(readings)
+    }
+}
@@ -0,0 +1,32 @@
+use crate::{AlsReading, Gain, Integration};
+
+/* Datasheet irradiance responsivity in tenths of counts per µW/cm², measured at high gain
+ * and 100ms integration: CH0 against white light and CH1 against its 850nm IR peak.
+ */
+const CH0_RESPONSIVITY: u64 = 2641;
+const CH1_RESPONSIVITY: u64 = 1541;
+
+// Irradiance seen by each photodiode, in nW/cm² (thousandths of µW/cm²)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Irradiance {
+    pub full_spectrum: u32,
+    pub infrared: u32,
+}
+
+impl Irradiance {
+    pub fn from_reading(reading: AlsReading, gain: Gain, integration: Integration) -> Self {
+        // Responsivity scales linearly with gain and integration time from the datasheet setting
+        let sensitivity = gain.multiplier() as u64 * integration.millis() as u64;
+        let reference = Gain::High.multiplier() as u64 * Integration::T100ms.millis() as u64;
+
+        let convert = |counts: u16, responsivity: u64| {
+            let nw = counts as u64 * 1000 * 10 * reference / (responsivity * sensitivity);
+            nw.min(u32::MAX as u64) as u32
+        };
+
+        Irradiance {
+            full_spectrum: convert(reading.full_spectrum(), CH0_RESPONSIVITY),
+            infrared: convert(reading.infrared(), CH1_RESPONSIVITY),
+        }
+    }
+}
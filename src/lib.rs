@@ -1,16 +1,38 @@
 #![no_std]
 
-use duplicate::duplicate_item;
+use duplicate::{duplicate, duplicate_item};
+use register::{Enable, Register, WritableRegister};
 
-// Used just to combine individual bits, might have to look into the bitfield crate
+mod auto_range;
+mod calibration;
+mod fusion;
+mod irradiance;
+mod light_source;
+mod lux;
+mod lux_model;
+mod metering;
+pub mod mux;
+pub mod register;
+pub mod typestate;
+
+pub use auto_range::AutoRange;
+pub use calibration::Calibration;
+pub use fusion::{FusedReading, Fusion, FusionStrategy, SensorHealth};
+pub use irradiance::Irradiance;
+pub use light_source::{Classification, LightSource, LightSourceClassifier};
+pub use lux::Lux;
+pub use lux_model::{AdafruitLux, DefaultLux, Dn40Lux, LuxModel};
+pub use metering::{Ev, Exposure, LightMeter};
+
+// Used just to combine individual bits of the command byte
 macro_rules! bit {
     ($n:expr) => {
         1 << $n
     };
 }
 
-/* Currently a straight port from my Zephyr implementation.
- * Will look into a more Rusty way if there is one (perhaps using the bitfield crate?)
+/* Chip constants and register addresses.
+ * The bit layout of each register lives in the bitfield-based types of the register module.
  */
 #[allow(dead_code)]
 mod chip {
@@ -20,6 +42,7 @@ mod chip {
     pub const MAX_ADC: u16 = 65535;
     pub const MAX_ADC_100: u16 = 36863;
     pub const LUX_DF: u16 = 408;
+    pub const POLL_MS: u32 = 5;
 
     /* Available registers on the chip */
     pub mod reg {
@@ -47,36 +70,10 @@ mod chip {
     pub mod cmd {
         pub const NORMAL: u8 = bit!(7) | bit!(5);
         pub const SPECIAL: u8 = bit!(7) | bit!(6) | bit!(5);
-        pub const CLEAR_INT: u8 = SPECIAL | 0x7;
-    }
-
-    /* Enable: (0x00): NPIEN:7 | SAI:6 | Reserved:5 | AIEN:4 | Reserved:3:2 | AEN:1 | PON:0 */
-    pub mod enable {
-        pub const POWER_MASK: u8 = bit!(1) | bit!(0);
-        pub const POWER_ON: u8 = bit!(1) | bit!(0);
-        pub const POWER_OFF: u8 = 0;
-        pub const AEN_MASK: u8 = bit!(1);
-        pub const AEN_ON: u8 = bit!(1);
-        pub const AEN_OFF: u8 = 0;
-        pub const AIEN_MASK: u8 = bit!(4);
-        pub const AIEN_ON: u8 = bit!(4);
-        pub const AIEN_OFF: u8 = 0;
-    }
-
-    /* Config/Control: (0x01): SRESET:7 | Reserved:6 | AGAIN:5:4 | Reserved:3 | ATIME:2:0 */
-    pub mod config {
-        pub const SRESET: u8 = bit!(7);
-        pub const AGAIN_MASK: u8 = bit!(5) | bit!(4);
-        pub const ATIME_MASK: u8 = bit!(2) | bit!(1) | bit!(0);
-    }
-
-    /* Status: (0x13): Reserved:7:6 | NPINTR:5 | AINT:4 | Reserved:3:1 | AVALID:0 */
-    pub mod status {
-        pub const AVALID_MASK: u8 = bit!(0);
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Integration {
     T100ms = 0x00,
     T200ms = 0x01,
@@ -86,7 +83,7 @@ pub enum Integration {
     T600ms = 0x05,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Gain {
     Low = 0x00,
     Med = 0x10,
@@ -94,7 +91,40 @@ pub enum Gain {
     Max = 0x30,
 }
 
-#[derive(Clone, Copy)]
+impl Integration {
+    pub(crate) fn millis(self) -> u16 {
+        match self {
+            Integration::T100ms => 100,
+            Integration::T200ms => 200,
+            Integration::T300ms => 300,
+            Integration::T400ms => 400,
+            Integration::T500ms => 500,
+            Integration::T600ms => 600,
+        }
+    }
+
+    pub(crate) fn max_count(self) -> u16 {
+        // Saturation value is less when integration time is 100ms
+        if self == Integration::T100ms {
+            chip::MAX_ADC_100
+        } else {
+            chip::MAX_ADC
+        }
+    }
+}
+
+impl Gain {
+    pub(crate) fn multiplier(self) -> u16 {
+        match self {
+            Gain::Low => 1,
+            Gain::Med => 25,
+            Gain::High => 400,
+            Gain::Max => 9200,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Persist {
     F0 = 0x00,
     F1 = 0x01,
@@ -114,27 +144,212 @@ pub enum Persist {
     F60 = 0x0F,
 }
 
+// Special functions are issued by writing SPECIAL | function to the command register
+#[derive(Clone, Copy, Debug)]
+pub enum SpecialFunction {
+    ForceInterrupt = 0x04,
+    ClearAlsInterrupt = 0x06,
+    ClearAllInterrupts = 0x07,
+    ClearNpInterrupt = 0x0A,
+}
+
+// Raw ADC counts from both photodiodes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AlsReading {
+    // CH0: visible + infrared
+    full_spectrum: u16,
+
+    // CH1: infrared only
+    infrared: u16,
+}
+
+impl AlsReading {
+    pub fn new(full_spectrum: u16, infrared: u16) -> Self {
+        AlsReading {
+            full_spectrum,
+            infrared,
+        }
+    }
+
+    pub fn full_spectrum(&self) -> u16 {
+        self.full_spectrum
+    }
+
+    pub fn infrared(&self) -> u16 {
+        self.infrared
+    }
+
+    // Noise can push CH1 above CH0 in near darkness, so don't let that wrap around
+    pub fn visible(&self) -> u16 {
+        self.full_spectrum.saturating_sub(self.infrared)
+    }
+}
+
+// CH0 was mislabelled as visible here, it is actually full spectrum (visible + infrared)
+#[deprecated(note = "use AlsReading, whose full_spectrum() is what this called visible")]
 #[derive(Clone, Copy, Debug)]
 pub struct AlsData {
     pub visible: u16,
     pub infrared: u16,
 }
 
-// To get float value, use: integer + fractional/1_000_000
-pub struct Lux {
-    // Integer component of lux
-    pub integer: i32,
+#[allow(deprecated)]
+impl From<AlsReading> for AlsData {
+    fn from(reading: AlsReading) -> Self {
+        AlsData {
+            visible: reading.full_spectrum,
+            infrared: reading.infrared,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Status {
+    // ADC cycle complete and data is valid
+    pub avalid: bool,
+
+    // ALS interrupt (persist-filtered) is asserted
+    pub aint: bool,
+
+    // No-persist ALS interrupt is asserted
+    pub npintr: bool,
+}
+
+/* Settings as currently programmed on the chip (see `get_config`), or to be programmed in
+ * one go with `apply_config` / `with_config` using the builder methods below.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Config {
+    pub gain: Gain,
+    pub integration: Integration,
+    pub persist: Persist,
+
+    // Persist-filtered ALS interrupt thresholds (raw CH0 counts)
+    pub lower_threshold: u16,
+    pub upper_threshold: u16,
+
+    // No-persist ALS interrupt thresholds (raw CH0 counts)
+    pub np_lower_threshold: u16,
+    pub np_upper_threshold: u16,
+
+    pub powered_on: bool,
+    pub interrupt_enabled: bool,
+    pub np_interrupt_enabled: bool,
+    pub sleep_after_interrupt: bool,
+}
+
+// The chip's power-on defaults, except that the ADC is left running
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            gain: Gain::Low,
+            integration: Integration::T100ms,
+            persist: Persist::F0,
+            lower_threshold: 0,
+            upper_threshold: 0,
+            np_lower_threshold: 0,
+            np_upper_threshold: 0,
+            powered_on: true,
+            interrupt_enabled: false,
+            np_interrupt_enabled: false,
+            sleep_after_interrupt: false,
+        }
+    }
+}
+
+impl Config {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_gain(mut self, gain: Gain) -> Self {
+        self.gain = gain;
+        self
+    }
+
+    pub fn with_integration(mut self, integration: Integration) -> Self {
+        self.integration = integration;
+        self
+    }
+
+    pub fn with_persist(mut self, persist: Persist) -> Self {
+        self.persist = persist;
+        self
+    }
+
+    pub fn with_threshold(mut self, lower: u16, upper: u16) -> Self {
+        self.lower_threshold = lower;
+        self.upper_threshold = upper;
+        self
+    }
+
+    pub fn with_np_threshold(mut self, lower: u16, upper: u16) -> Self {
+        self.np_lower_threshold = lower;
+        self.np_upper_threshold = upper;
+        self
+    }
 
-    // Fractional component of lux (in one-millionth parts)
-    pub fractional: i32,
+    pub fn with_interrupt(mut self, enable: bool) -> Self {
+        self.interrupt_enabled = enable;
+        self
+    }
+
+    pub fn with_np_interrupt(mut self, enable: bool) -> Self {
+        self.np_interrupt_enabled = enable;
+        self
+    }
+
+    pub fn with_sleep_after_interrupt(mut self, enable: bool) -> Self {
+        self.sleep_after_interrupt = enable;
+        self
+    }
+
+    // Leave the chip powered down after applying, e.g. to start it later with power_on
+    pub fn with_powered_on(mut self, powered_on: bool) -> Self {
+        self.powered_on = powered_on;
+        self
+    }
 }
 
+// Band around the last CH0 reading that the light has to leave before the interrupt fires
 #[derive(Clone, Copy, Debug)]
+pub enum TrackingWindow {
+    // Percentage of the last CH0 reading
+    Relative(u8),
+
+    // Fixed number of CH0 counts
+    Absolute(u16),
+}
+
+impl TrackingWindow {
+    pub(crate) fn around(self, ch0: u16) -> (u16, u16) {
+        let band = match self {
+            // Keep at least one count of headroom so a dark reading doesn't fire every cycle
+            TrackingWindow::Relative(percent) => {
+                ((ch0 as u32 * percent as u32 / 100) as u16).max(1)
+            }
+            TrackingWindow::Absolute(counts) => counts,
+        };
+
+        (ch0.saturating_sub(band), ch0.saturating_add(band))
+    }
+}
+
+// Both threshold pairs are laid out as lower then upper, each little-endian
+fn threshold_bytes(lower: u16, upper: u16) -> [u8; 4] {
+    let lower = u16::to_le_bytes(lower);
+    let upper = u16::to_le_bytes(upper);
+    [lower[0], lower[1], upper[0], upper[1]]
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Error<E> {
     I2cError(E),
     InvalidId(u8),
-    AdcSaturated(AlsData),
+    InvalidConfig(u8),
+    AdcSaturated(AlsReading),
     CycleIncomplete,
+    PinError,
 }
 
 impl<E> From<E> for Error<E> {
@@ -143,54 +358,57 @@ impl<E> From<E> for Error<E> {
     }
 }
 
-/* These two duplicate_item blocks are here because we need separate types and impls for
+// Placeholder for Tsl2591Async when no interrupt pin has been handed to the driver
+pub struct NoIntPin;
+
+/* These duplicate blocks are here because we need separate types and impls for
  * async vs blocking, but don't want to duplicate all this code just to add async/await.
+ * Only the async driver can own an interrupt pin, hence the extra generic parameter.
+ * The lux model is held by value, so its coefficients can come from anywhere at runtime.
  */
-#[duplicate_item(
-    _tsl2591_;
-    [Tsl2591];
-    [Tsl2591Async];
-)]
-pub struct _tsl2591_<I> {
-    i2c: I,
-    again: u16,
-    atime: u16,
-    pub powered_on: bool,
+duplicate! {
+    [
+        _tsl2591_ _generics_ _int_pin_field_;
+        [Tsl2591] [I, M = DefaultLux] [];
+        [Tsl2591Async] [I, P = NoIntPin, M = DefaultLux] [int_pin: P,];
+    ]
+    pub struct _tsl2591_<_generics_> {
+        i2c: I,
+        gain: Gain,
+        integration: Integration,
+        auto_range: Option<AutoRange>,
+        lux_threshold: Option<(i64, i64)>,
+        tracking: Option<TrackingWindow>,
+        lux_model: M,
+        calibration: Calibration,
+        pub powered_on: bool,
+        _int_pin_field_
+    }
 }
 
 #[duplicate_item(
-    _tsl2591_ _hal_ async add_await(code);
-    [Tsl2591] [embedded_hal] [] [code];
-    [Tsl2591Async] [embedded_hal_async] [async] [code.await];
+    _tsl2591_ _hal_ async add_await(code) _powered_on_init_;
+    [Tsl2591] [embedded_hal] [] [code] [powered_on: false];
+    [Tsl2591Async] [embedded_hal_async] [async] [code.await] [powered_on: false, int_pin: NoIntPin];
 )]
 impl<I: _hal_::i2c::I2c> _tsl2591_<I> {
-    fn map_again(again: Gain) -> u16 {
-        match again {
-            Gain::Low => 1,
-            Gain::Med => 25,
-            Gain::High => 400,
-            Gain::Max => 9200,
-        }
-    }
-
-    fn map_atime(atime: Integration) -> u16 {
-        match atime {
-            Integration::T100ms => 100,
-            Integration::T200ms => 200,
-            Integration::T300ms => 300,
-            Integration::T400ms => 400,
-            Integration::T500ms => 500,
-            Integration::T600ms => 600,
+    fn detached(i2c: I) -> _tsl2591_<I> {
+        _tsl2591_ {
+            i2c,
+            gain: Gain::Low,
+            integration: Integration::T100ms,
+            auto_range: None,
+            lux_threshold: None,
+            tracking: None,
+            lux_model: DefaultLux,
+            calibration: Calibration::IDENTITY,
+            // Also starts the async driver off with no interrupt pin
+            _powered_on_init_,
         }
     }
 
     pub async fn new(i2c: I) -> Result<_tsl2591_<I>, Error<I::Error>> {
-        let mut tsl2591 = _tsl2591_ {
-            i2c,
-            again: Self::map_again(Gain::Low),
-            atime: Self::map_atime(Integration::T100ms),
-            powered_on: false,
-        };
+        let mut tsl2591 = Self::detached(i2c);
         add_await([tsl2591.reset()])?;
 
         let id = add_await([tsl2591.get_id()])?;
@@ -202,129 +420,395 @@ impl<I: _hal_::i2c::I2c> _tsl2591_<I> {
         Ok(tsl2591)
     }
 
-    pub async fn write(&mut self, reg: u8, val: u8) -> Result<(), Error<I::Error>> {
+    /* Takes over a chip without resetting it, e.g. after a bootloader jump or when the bus was
+     * handed to another driver in between. Cached settings are read back from the chip.
+     */
+    pub async fn attach(i2c: I) -> Result<_tsl2591_<I>, Error<I::Error>> {
+        let mut tsl2591 = Self::detached(i2c);
+
+        let id = add_await([tsl2591.get_id()])?;
+        if id != chip::DEV_ID {
+            return Err(Error::InvalidId(id));
+        }
+        add_await([tsl2591.sync_from_chip()])?;
+
+        Ok(tsl2591)
+    }
+
+    pub async fn with_config(i2c: I, config: Config) -> Result<_tsl2591_<I>, Error<I::Error>> {
+        let mut tsl2591 = add_await([Self::new(i2c)])?;
+        add_await([tsl2591.apply_config(config)])?;
+
+        Ok(tsl2591)
+    }
+}
+
+#[duplicate_item(
+    _tsl2591_ _hal_ async add_await(code) _generics_ _args_ _with_model_(model)
+        _powered_on_move_;
+    [Tsl2591] [embedded_hal] [] [code] [I: embedded_hal::i2c::I2c, M: LuxModel] [I, M]
+        [I, model] [powered_on: self.powered_on];
+    [Tsl2591Async] [embedded_hal_async] [async] [code.await]
+        [I: embedded_hal_async::i2c::I2c, P, M: LuxModel] [I, P, M] [I, P, model]
+        [powered_on: self.powered_on, int_pin: self.int_pin];
+)]
+impl<_generics_> _tsl2591_<_args_> {
+    // Gives the bus back, leaving the chip in whatever state it is in
+    pub fn destroy(self) -> I {
+        self.i2c
+    }
+
+    /* Swaps in a different kind of lux model, e.g. right after the constructor. Lux thresholds
+     * that were already set keep their old counts until the next set_lux_model, set_threshold_lux
+     * or gain/integration change, so pick the model before setting them.
+     */
+    pub fn with_lux_model<N: LuxModel>(self, lux_model: N) -> _tsl2591_<_with_model_([N])> {
+        _tsl2591_ {
+            i2c: self.i2c,
+            gain: self.gain,
+            integration: self.integration,
+            auto_range: self.auto_range,
+            lux_threshold: self.lux_threshold,
+            tracking: self.tracking,
+            lux_model,
+            calibration: self.calibration,
+            // Also carries over the async driver's interrupt pin
+            _powered_on_move_,
+        }
+    }
+
+    fn track_register(&mut self, addr: u8, bits: u8) {
+        // Keep cached driver state in step with whatever was just written to the chip
+        match addr {
+            chip::reg::ENABLE => {
+                let enable = Enable::from_bits(bits);
+                self.powered_on = enable.pon() && enable.aen();
+            }
+            chip::reg::CONFIG => {
+                let config = register::ControlReg::from_bits(bits);
+                if config.sreset() {
+                    // Software reset returns every register to its power-on default
+                    self.gain = Gain::Low;
+                    self.integration = Integration::T100ms;
+                    self.powered_on = false;
+                } else {
+                    self.gain = config.gain();
+                    if let Some(time) = config.integration() {
+                        self.integration = time;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    async fn write(&mut self, reg: u8, val: u8) -> Result<(), Error<I::Error>> {
         add_await([self
             .i2c
             .write(chip::I2C_ADDR, &[chip::cmd::NORMAL | reg, val])])?;
         Ok(())
     }
 
-    pub async fn read(&mut self, reg: u8, buf: &mut [u8]) -> Result<(), Error<I::Error>> {
+    async fn read(&mut self, reg: u8, buf: &mut [u8]) -> Result<(), Error<I::Error>> {
         add_await([self
             .i2c
             .write_read(chip::I2C_ADDR, &[chip::cmd::NORMAL | reg], buf)])?;
         Ok(())
     }
 
-    pub async fn update(&mut self, reg: u8, mask: u8, val: u8) -> Result<(), Error<I::Error>> {
-        let mut old_value = [0u8; 1];
-        add_await([self.read(reg, &mut old_value)])?;
+    pub async fn read_register<R: Register>(&mut self) -> Result<R, Error<I::Error>> {
+        let mut value = [0u8; 1];
+        add_await([self.read(R::ADDR, &mut value)])?;
+        Ok(R::from_bits(value[0]))
+    }
 
-        let new_value = (old_value[0] & !mask) | (val & mask);
-        if new_value != old_value[0] {
-            add_await([self.write(reg, new_value)])?;
-        }
+    pub async fn write_register<R: WritableRegister>(
+        &mut self,
+        value: R,
+    ) -> Result<(), Error<I::Error>> {
+        let bits = value.bits() & R::WRITE_MASK;
+        add_await([self.write(R::ADDR, bits)])?;
 
+        self.track_register(R::ADDR, bits);
         Ok(())
     }
 
-    pub async fn power_on(&mut self) -> Result<(), Error<I::Error>> {
-        add_await([self.update(
-            chip::reg::ENABLE,
-            chip::enable::POWER_MASK,
-            chip::enable::POWER_ON,
-        )])?;
+    pub async fn modify_register<R: WritableRegister>(
+        &mut self,
+        f: impl FnOnce(&mut R),
+    ) -> Result<(), Error<I::Error>> {
+        let old_value: R = add_await([self.read_register()])?;
+        let mut new_value = old_value;
+        f(&mut new_value);
+
+        // Skip the write if nothing actually changed
+        let bits = new_value.bits() & R::WRITE_MASK;
+        if bits != old_value.bits() & R::WRITE_MASK {
+            add_await([self.write(R::ADDR, bits)])?;
+        }
 
-        self.powered_on = true;
+        self.track_register(R::ADDR, bits);
         Ok(())
     }
 
+    pub async fn power_on(&mut self) -> Result<(), Error<I::Error>> {
+        add_await([self.modify_register(|enable: &mut Enable| {
+            enable.set_pon(true);
+            enable.set_aen(true);
+        })])
+    }
+
     pub async fn power_off(&mut self) -> Result<(), Error<I::Error>> {
-        add_await([self.update(
-            chip::reg::ENABLE,
-            chip::enable::POWER_MASK,
-            chip::enable::POWER_OFF,
-        )])?;
+        add_await([self.modify_register(|enable: &mut Enable| {
+            enable.set_pon(false);
+            enable.set_aen(false);
+        })])
+    }
+
+    // Settings are changed with the ADC powered down, but only power it back up if it was running
+    async fn resume(&mut self, powered_on: bool) -> Result<(), Error<I::Error>> {
+        if powered_on {
+            add_await([self.power_on()])?;
+        }
 
-        self.powered_on = false;
         Ok(())
     }
 
     pub async fn reset(&mut self) -> Result<(), Error<I::Error>> {
+        let mut config = register::ControlReg::default();
+        config.set_sreset(true);
+
         add_await([self.power_off()])?;
-        add_await([self.write(chip::reg::CONFIG, chip::config::SRESET)])?;
+        add_await([self.write_register(config)])?;
         add_await([self.power_on()])?;
 
         Ok(())
     }
 
     pub async fn get_id(&mut self) -> Result<u8, Error<I::Error>> {
-        let mut device_id = [0u8; 1];
-        add_await([self.read(chip::reg::ID, &mut device_id)])?;
-        Ok(device_id[0])
+        let id: register::Id = add_await([self.read_register()])?;
+        Ok(id.id())
     }
 
-    pub async fn set_again(&mut self, gain: Gain) -> Result<(), Error<I::Error>> {
+    async fn rescale(
+        &mut self,
+        f: impl FnOnce(&mut register::ControlReg),
+    ) -> Result<(), Error<I::Error>> {
+        let powered_on = self.powered_on;
         add_await([self.power_off()])?;
-        add_await([self.update(chip::reg::CONFIG, chip::config::AGAIN_MASK, gain as u8)])?;
-        add_await([self.power_on()])?;
+        add_await([self.modify_register(f)])?;
+
+        // Lux thresholds map to different CH0 counts now that the scale has changed
+        add_await([self.refresh_lux_threshold()])?;
+        add_await([self.resume(powered_on)])?;
 
-        self.again = Self::map_again(gain);
         Ok(())
     }
 
+    pub async fn set_again(&mut self, gain: Gain) -> Result<(), Error<I::Error>> {
+        add_await([self.rescale(|config| config.set_gain(gain))])
+    }
+
     pub async fn set_atime(&mut self, time: Integration) -> Result<(), Error<I::Error>> {
-        add_await([self.power_off()])?;
-        add_await([self.update(chip::reg::CONFIG, chip::config::ATIME_MASK, time as u8)])?;
-        add_await([self.power_on()])?;
+        add_await([self.rescale(|config| config.set_integration(time))])
+    }
 
-        self.atime = Self::map_atime(time);
-        Ok(())
+    pub fn set_auto_range(&mut self, auto_range: Option<AutoRange>) {
+        self.auto_range = auto_range;
+    }
+
+    async fn set_range(&mut self, gain: Gain, time: Integration) -> Result<(), Error<I::Error>> {
+        // Change both gain and integration time within a single power-down window
+        add_await([self.rescale(|config| {
+            config.set_gain(gain);
+            config.set_integration(time);
+        })])
     }
 
     pub async fn set_persist(&mut self, persist: Persist) -> Result<(), Error<I::Error>> {
+        let mut apers = register::PersistReg::default();
+        apers.set_persist(persist);
+
+        let powered_on = self.powered_on;
         add_await([self.power_off()])?;
-        add_await([self.write(chip::reg::PERSIST, persist as u8)])?;
-        add_await([self.power_on()])?;
+        add_await([self.write_register(apers)])?;
+        add_await([self.resume(powered_on)])?;
 
         Ok(())
     }
 
     pub async fn set_threshold(&mut self, lower: u16, upper: u16) -> Result<(), Error<I::Error>> {
-        // Is there a more idiomatic way to concatenate two arrays plus another value?
-        let lower = u16::to_le_bytes(lower);
-        let upper = u16::to_le_bytes(upper);
-        let buf = [
-            chip::cmd::NORMAL | chip::reg::AILTL,
-            lower[0],
-            lower[1],
-            upper[0],
-            upper[1],
-        ];
+        // Raw counts take over from any lux thresholds set previously
+        self.lux_threshold = None;
 
+        let powered_on = self.powered_on;
         add_await([self.power_off()])?;
+        add_await([self.write_threshold(chip::reg::AILTL, lower, upper)])?;
+        add_await([self.resume(powered_on)])?;
+
+        Ok(())
+    }
+
+    pub async fn set_threshold_lux(
+        &mut self,
+        lower: Lux,
+        upper: Lux,
+    ) -> Result<(), Error<I::Error>> {
+        self.lux_threshold = Some((lower.micro_lux(), upper.micro_lux()));
+
+        let powered_on = self.powered_on;
+        add_await([self.power_off()])?;
+        add_await([self.refresh_lux_threshold()])?;
+        add_await([self.resume(powered_on)])?;
+
+        Ok(())
+    }
+
+    async fn refresh_lux_threshold(&mut self) -> Result<(), Error<I::Error>> {
+        if let Some((lower, upper)) = self.lux_threshold {
+            let lower = self.lux_to_counts(lower);
+            let upper = self.lux_to_counts(upper);
+            add_await([self.write_threshold(chip::reg::AILTL, lower, upper)])?;
+        }
+
+        Ok(())
+    }
+
+    fn lux_to_counts(&self, micro_lux: i64) -> u16 {
+        let micro_lux = self.calibration.invert_micro(micro_lux);
+        lux_model::counts_for(&self.lux_model, micro_lux, self.gain, self.integration)
+    }
+
+    // Writes a lower/upper pair starting at AILTL or NPAILTL
+    async fn write_threshold(
+        &mut self,
+        reg: u8,
+        lower: u16,
+        upper: u16,
+    ) -> Result<(), Error<I::Error>> {
+        let [lower_l, lower_h, upper_l, upper_h] = threshold_bytes(lower, upper);
+        let buf = [chip::cmd::NORMAL | reg, lower_l, lower_h, upper_l, upper_h];
+
         add_await([self.i2c.write(chip::I2C_ADDR, &buf)])?;
-        add_await([self.power_on()])?;
 
         Ok(())
     }
 
-    pub async fn is_cycle_complete(&mut self) -> Result<bool, Error<I::Error>> {
-        let mut status = [0u8; 1];
-        add_await([self.read(chip::reg::STATUS, &mut status)])?;
+    pub async fn set_np_threshold(
+        &mut self,
+        lower: u16,
+        upper: u16,
+    ) -> Result<(), Error<I::Error>> {
+        // No-persist thresholds bypass the persist filter, so they fire on the very next cycle
+        let powered_on = self.powered_on;
+        add_await([self.power_off()])?;
+        add_await([self.write_threshold(chip::reg::NPAILTL, lower, upper)])?;
+        add_await([self.resume(powered_on)])?;
+
+        Ok(())
+    }
+
+    pub async fn get_config(&mut self) -> Result<Config, Error<I::Error>> {
+        // ENABLE/CONFIG and the four threshold pairs are contiguous, so read each in one shot
+        let mut control = [0u8; 2];
+        add_await([self.read(chip::reg::ENABLE, &mut control)])?;
+        let mut thresholds = [0u8; 8];
+        add_await([self.read(chip::reg::AILTL, &mut thresholds)])?;
+        let persist: register::PersistReg = add_await([self.read_register()])?;
+
+        let enable = Enable::from_bits(control[0]);
+        let config = register::ControlReg::from_bits(control[1]);
+        let integration = config
+            .integration()
+            .ok_or(Error::InvalidConfig(control[1]))?;
+
+        Ok(Config {
+            gain: config.gain(),
+            integration,
+            persist: persist.persist(),
+            lower_threshold: u16::from_le_bytes([thresholds[0], thresholds[1]]),
+            upper_threshold: u16::from_le_bytes([thresholds[2], thresholds[3]]),
+            np_lower_threshold: u16::from_le_bytes([thresholds[4], thresholds[5]]),
+            np_upper_threshold: u16::from_le_bytes([thresholds[6], thresholds[7]]),
+            powered_on: enable.pon() && enable.aen(),
+            interrupt_enabled: enable.aien(),
+            np_interrupt_enabled: enable.npien(),
+            sleep_after_interrupt: enable.sai(),
+        })
+    }
+
+    pub async fn apply_config(&mut self, config: Config) -> Result<(), Error<I::Error>> {
+        /* Three writes in total: power down along with the new gain and integration time,
+         * then both threshold pairs and persist in one burst (AILTL..PERSIST are contiguous),
+         * then ENABLE to power back up with the requested interrupts.
+         */
+        let mut control = register::ControlReg::default();
+        control.set_gain(config.gain);
+        control.set_integration(config.integration);
+        let control = control.bits() & register::ControlReg::WRITE_MASK;
 
+        let buf = [chip::cmd::NORMAL | chip::reg::ENABLE, 0, control];
+        add_await([self.i2c.write(chip::I2C_ADDR, &buf)])?;
+        self.track_register(chip::reg::ENABLE, 0);
+        self.track_register(chip::reg::CONFIG, control);
+
+        let mut apers = register::PersistReg::default();
+        apers.set_persist(config.persist);
+
+        let threshold = threshold_bytes(config.lower_threshold, config.upper_threshold);
+        let np_threshold = threshold_bytes(config.np_lower_threshold, config.np_upper_threshold);
+        let mut buf = [0u8; 10];
+        buf[0] = chip::cmd::NORMAL | chip::reg::AILTL;
+        buf[1..5].copy_from_slice(&threshold);
+        buf[5..9].copy_from_slice(&np_threshold);
+        buf[9] = apers.bits();
+        add_await([self.i2c.write(chip::I2C_ADDR, &buf)])?;
+
+        // Raw counts take over from any lux thresholds set previously
+        self.lux_threshold = None;
+
+        let mut enable = Enable::default();
+        enable.set_pon(config.powered_on);
+        enable.set_aen(config.powered_on);
+        enable.set_aien(config.interrupt_enabled);
+        enable.set_npien(config.np_interrupt_enabled);
+        enable.set_sai(config.sleep_after_interrupt);
+        add_await([self.write_register(enable)])?;
+
+        Ok(())
+    }
+
+    pub async fn sync_from_chip(&mut self) -> Result<(), Error<I::Error>> {
+        /* Refresh cached gain, integration time and power state from the chip itself,
+         * e.g. after a brown-out or after another bus master changed the settings.
+         */
+        let config = add_await([self.get_config()])?;
+        self.gain = config.gain;
+        self.integration = config.integration;
+        self.powered_on = config.powered_on;
+
+        Ok(())
+    }
+
+    pub async fn get_status(&mut self) -> Result<Status, Error<I::Error>> {
+        let status: register::StatusReg = add_await([self.read_register()])?;
+
+        Ok(Status {
+            avalid: status.avalid(),
+            aint: status.aint(),
+            npintr: status.npintr(),
+        })
+    }
+
+    pub async fn is_cycle_complete(&mut self) -> Result<bool, Error<I::Error>> {
         // Checking if the AVALID bit is high (cycle complete) or not (cycle incomplete)
-        if status[0] & chip::status::AVALID_MASK == 0 {
-            Ok(false)
-        } else {
-            Ok(true)
-        }
+        Ok(add_await([self.get_status()])?.avalid)
     }
 
     pub async fn get_raw_als_data(
         &mut self,
         check_complete: bool,
-    ) -> Result<AlsData, Error<I::Error>> {
+    ) -> Result<AlsReading, Error<I::Error>> {
         /* If the user wishes, check to make sure there is valid data ready to be read.
          * The sensor will set the AVALID bit when integration cycle is complete.
          * If it's set, read the data and re-assert the AEN bit to reset for next read.
@@ -335,37 +819,34 @@ impl<I: _hal_::i2c::I2c> _tsl2591_<I> {
             }
 
             // Re-assert AEN bit to check completion of next reading
-            add_await([self.update(
-                chip::reg::ENABLE,
-                chip::enable::AEN_MASK,
-                chip::enable::AEN_OFF,
-            )])?;
-            add_await([self.update(
-                chip::reg::ENABLE,
-                chip::enable::AEN_MASK,
-                chip::enable::AEN_ON,
-            )])?;
+            add_await([self.restart_cycle()])?;
         }
 
         // Reads C0DATAL, C0DATAH, C1DATAL, and C1DATAH all in one shot
         let mut als_data = [0u8; 4];
         add_await([self.read(chip::reg::C0DATAL, &mut als_data)])?;
 
-        // Convert buffer to visible and infrared u16's
-        let als_data = AlsData {
-            visible: u16::from_le_bytes([als_data[0], als_data[1]]),
-            infrared: u16::from_le_bytes([als_data[2], als_data[3]]),
-        };
+        // Convert buffer to full spectrum and infrared u16's
+        let als_data = AlsReading::new(
+            u16::from_le_bytes([als_data[0], als_data[1]]),
+            u16::from_le_bytes([als_data[2], als_data[3]]),
+        );
 
-        // Saturation value is less when integration time is 100ms
-        let max_count = if self.atime == 100 {
-            chip::MAX_ADC_100
-        } else {
-            chip::MAX_ADC
-        };
+        let max_count = self.integration.max_count();
+        let saturated = als_data.full_spectrum >= max_count || als_data.infrared >= max_count;
+
+        // Retune for the next cycle now that this reading has been taken
+        if let Some(auto_range) = self.auto_range {
+            let counts = als_data.full_spectrum.max(als_data.infrared);
+            if let Some((gain, time)) =
+                auto_range.next_setting(self.gain, self.integration, counts, saturated)
+            {
+                add_await([self.set_range(gain, time)])?;
+            }
+        }
 
         // Return the data even if it's saturated just in case user wants to use it anyway
-        if als_data.visible >= max_count || als_data.infrared >= max_count {
+        if saturated {
             Err(Error::AdcSaturated(als_data))
         } else {
             Ok(als_data)
@@ -373,42 +854,305 @@ impl<I: _hal_::i2c::I2c> _tsl2591_<I> {
     }
 
     pub async fn get_lux(&mut self, check_complete: bool) -> Result<Lux, Error<I::Error>> {
+        // Auto-ranging may retune while reading, so hold on to the settings for this reading
+        let (gain, integration) = (self.gain, self.integration);
+
         // Will return early if saturated, since no point in calculating lux
         let als_data = add_await([self.get_raw_als_data(check_complete)])?;
 
-        // Will work on making this look a bit nicer
-        let cpl: i64 = (self.atime as i64 * self.again as i64) * 1_000_000;
-        let strength: i64 = if als_data.visible > 0 {
-            (((als_data.visible as i64) - (als_data.infrared as i64))
-                * (1_000_000
-                    - (((als_data.infrared as i64) * 1_000_000) / (als_data.visible as i64))))
-                * chip::LUX_DF as i64
-        } else {
-            0
+        Ok(self.compute_lux(als_data, gain, integration))
+    }
+
+    pub async fn get_irradiance(
+        &mut self,
+        check_complete: bool,
+    ) -> Result<Irradiance, Error<I::Error>> {
+        let (gain, integration) = (self.gain, self.integration);
+        let als_data = add_await([self.get_raw_als_data(check_complete)])?;
+
+        Ok(Irradiance::from_reading(als_data, gain, integration))
+    }
+
+    #[cfg(feature = "float")]
+    #[duplicate_item(
+        get_lux_float lux_float apply_float float;
+        [get_lux_f32] [lux_f32] [apply_f32] [f32];
+        [get_lux_f64] [lux_f64] [apply_f64] [f64];
+    )]
+    pub async fn get_lux_float(&mut self, check_complete: bool) -> Result<float, Error<I::Error>> {
+        let (gain, integration) = (self.gain, self.integration);
+        let als_data = add_await([self.get_raw_als_data(check_complete)])?;
+
+        let lux = self.lux_model.lux_float(als_data, gain, integration);
+        Ok(self.calibration.apply_float(lux))
+    }
+
+    // Replaces the model with another of the same kind, e.g. Dn40Lux with new coefficients
+    pub async fn set_lux_model(&mut self, model: M) -> Result<(), Error<I::Error>> {
+        self.lux_model = model;
+
+        // Lux thresholds were converted to counts with the previous model
+        if self.lux_threshold.is_some() {
+            let powered_on = self.powered_on;
+            add_await([self.power_off()])?;
+            add_await([self.refresh_lux_threshold()])?;
+            add_await([self.resume(powered_on)])?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn set_calibration(
+        &mut self,
+        calibration: Calibration,
+    ) -> Result<(), Error<I::Error>> {
+        self.calibration = calibration;
+
+        if self.lux_threshold.is_some() {
+            let powered_on = self.powered_on;
+            add_await([self.power_off()])?;
+            add_await([self.refresh_lux_threshold()])?;
+            add_await([self.resume(powered_on)])?;
+        }
+
+        Ok(())
+    }
+
+    fn compute_lux(&self, als_data: AlsReading, gain: Gain, integration: Integration) -> Lux {
+        let lux = self.lux_model.lux(als_data, gain, integration);
+        self.calibration.apply(lux)
+    }
+
+    pub fn set_tracking_window(&mut self, window: Option<TrackingWindow>) {
+        self.tracking = window;
+    }
+
+    async fn recentre_threshold(
+        &mut self,
+        ch0: u16,
+        gain: Gain,
+        integration: Integration,
+    ) -> Result<(), Error<I::Error>> {
+        if let Some(window) = self.tracking {
+            // Auto-ranging may have retuned since the reading, so bring it to the current scale
+            let from = gain.multiplier() as u64 * integration.millis() as u64;
+            let to = self.gain.multiplier() as u64 * self.integration.millis() as u64;
+            let ch0 = (ch0 as u64 * to / from).min(u16::MAX as u64) as u16;
+
+            let (lower, upper) = window.around(ch0);
+            add_await([self.set_threshold(lower, upper)])?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn get_lux_and_track(&mut self) -> Result<Lux, Error<I::Error>> {
+        /* Meant to be called once the ALS interrupt fires: read the sample that raised it,
+         * re-centre the threshold window around it, then clear the interrupt.
+         */
+        let (gain, integration) = (self.gain, self.integration);
+        let result = add_await([self.get_raw_als_data(false)]);
+        let als_data = match result {
+            Ok(als_data) | Err(Error::AdcSaturated(als_data)) => als_data,
+            Err(error) => return Err(error),
         };
 
-        /* Avoided using floating point math just in case architecture does not support it.
-         * Instead return a struct representing integer and fractional components of lux.
+        add_await([self.recentre_threshold(als_data.full_spectrum, gain, integration)])?;
+        add_await([self.special_function(SpecialFunction::ClearAlsInterrupt)])?;
+
+        result.map(|als_data| self.compute_lux(als_data, gain, integration))
+    }
+
+    async fn restart_cycle(&mut self) -> Result<(), Error<I::Error>> {
+        // Toggling AEN clears AVALID and starts a fresh integration cycle
+        add_await([self.modify_register(|enable: &mut Enable| enable.set_aen(false))])?;
+        add_await([self.modify_register(|enable: &mut Enable| enable.set_aen(true))])?;
+
+        Ok(())
+    }
+
+    pub async fn wait_for_cycle<D: _hal_::delay::DelayNs>(
+        &mut self,
+        delay: &mut D,
+    ) -> Result<(), Error<I::Error>> {
+        /* The actual cycle runs slightly longer than the nominal integration time, so after
+         * sleeping for it poll AVALID, giving up after another full integration time.
          */
-        Ok(Lux {
-            integer: (strength / cpl) as i32,
-            fractional: (((strength % cpl) * 1_000_000) / cpl) as i32,
-        })
+        let atime = self.integration.millis() as u32;
+        add_await([delay.delay_ms(atime)]);
+
+        let mut waited = 0;
+        while !add_await([self.is_cycle_complete()])? {
+            if waited >= atime {
+                return Err(Error::CycleIncomplete);
+            }
+
+            add_await([delay.delay_ms(chip::POLL_MS)]);
+            waited += chip::POLL_MS;
+        }
+
+        Ok(())
+    }
+
+    pub async fn measure_lux<D: _hal_::delay::DelayNs>(
+        &mut self,
+        delay: &mut D,
+    ) -> Result<Lux, Error<I::Error>> {
+        // With auto-ranging enabled keep measuring until the settings stop changing
+        let mut attempts = 0;
+        loop {
+            let (gain, integration) = (self.gain, self.integration);
+
+            add_await([self.restart_cycle()])?;
+            add_await([self.wait_for_cycle(delay)])?;
+            let lux = add_await([self.get_lux(true)]);
+
+            attempts += 1;
+            let retuned = gain != self.gain || integration != self.integration;
+            if self.auto_range.is_none() || !retuned || attempts >= auto_range::MAX_STEPS {
+                return lux;
+            }
+        }
     }
 
     pub async fn enable_interrupt(&mut self, enable: bool) -> Result<(), Error<I::Error>> {
-        let aien = if enable {
-            chip::enable::AIEN_ON
-        } else {
-            chip::enable::AIEN_OFF
-        };
+        add_await([self.modify_register(|enable_reg: &mut Enable| enable_reg.set_aien(enable))])?;
+        Ok(())
+    }
 
-        add_await([self.update(chip::reg::ENABLE, chip::enable::AIEN_MASK, aien)])?;
+    pub async fn special_function(
+        &mut self,
+        function: SpecialFunction,
+    ) -> Result<(), Error<I::Error>> {
+        add_await([self
+            .i2c
+            .write(chip::I2C_ADDR, &[chip::cmd::SPECIAL | function as u8])])?;
         Ok(())
     }
 
     pub async fn clear_interrupt(&mut self) -> Result<(), Error<I::Error>> {
-        add_await([self.i2c.write(chip::I2C_ADDR, &[chip::cmd::CLEAR_INT])])?;
+        add_await([self.special_function(SpecialFunction::ClearAllInterrupts)])
+    }
+
+    pub async fn enable_np_interrupt(&mut self, enable: bool) -> Result<(), Error<I::Error>> {
+        add_await([self.modify_register(|enable_reg: &mut Enable| enable_reg.set_npien(enable))])?;
+        Ok(())
+    }
+
+    pub async fn clear_np_interrupt(&mut self) -> Result<(), Error<I::Error>> {
+        add_await([self.special_function(SpecialFunction::ClearNpInterrupt)])
+    }
+
+    pub async fn enable_sleep_after_interrupt(
+        &mut self,
+        enable: bool,
+    ) -> Result<(), Error<I::Error>> {
+        add_await([self.modify_register(|enable_reg: &mut Enable| enable_reg.set_sai(enable))])?;
+        Ok(())
+    }
+
+    pub async fn clear_interrupt_and_resume(&mut self) -> Result<(), Error<I::Error>> {
+        /* With SAI set the chip stops integrating once an interrupt fires, and only resumes
+         * once the interrupt is cleared. Make sure the ADC is enabled again in case it was
+         * turned off while the chip was asleep.
+         */
+        add_await([self.clear_interrupt()])?;
+        add_await([self.power_on()])?;
+
         Ok(())
     }
 }
+
+impl<I: embedded_hal_async::i2c::I2c, P, M> Tsl2591Async<I, P, M> {
+    // Like destroy, but also gives back the interrupt pin
+    pub fn free(self) -> (I, P) {
+        (self.i2c, self.int_pin)
+    }
+
+    pub fn with_int_pin<W: embedded_hal_async::digital::Wait>(
+        self,
+        int_pin: W,
+    ) -> Tsl2591Async<I, W, M> {
+        Tsl2591Async {
+            i2c: self.i2c,
+            gain: self.gain,
+            integration: self.integration,
+            auto_range: self.auto_range,
+            lux_threshold: self.lux_threshold,
+            tracking: self.tracking,
+            lux_model: self.lux_model,
+            calibration: self.calibration,
+            powered_on: self.powered_on,
+            int_pin,
+        }
+    }
+}
+
+impl<I: embedded_hal_async::i2c::I2c, P: embedded_hal_async::digital::Wait, M: LuxModel>
+    Tsl2591Async<I, P, M>
+{
+    pub async fn wait_for_threshold_event(&mut self) -> Result<Lux, Error<I::Error>> {
+        // INT is active low and stays asserted until cleared, so don't wait on an edge
+        self.int_pin
+            .wait_for_low()
+            .await
+            .map_err(|_| Error::PinError)?;
+
+        // Only clear the interrupt source(s) that are actually driving the pin
+        let status = self.get_status().await?;
+        let enable: Enable = self.read_register().await?;
+        let aint = status.aint && enable.aien();
+        let npintr = status.npintr && enable.npien();
+
+        // Interrupts are raised at the end of a cycle, so the data is already valid
+        let (gain, integration) = (self.gain, self.integration);
+        let result = self.get_raw_als_data(false).await;
+        if aint {
+            if let Ok(als_data) | Err(Error::AdcSaturated(als_data)) = result {
+                self.recentre_threshold(als_data.full_spectrum, gain, integration)
+                    .await?;
+            }
+        }
+
+        let function = match (aint, npintr) {
+            (true, true) => Some(SpecialFunction::ClearAllInterrupts),
+            (true, false) => Some(SpecialFunction::ClearAlsInterrupt),
+            (false, true) => Some(SpecialFunction::ClearNpInterrupt),
+            (false, false) => None,
+        };
+        if let Some(function) = function {
+            self.special_function(function).await?;
+        }
+
+        result.map(|als_data| self.compute_lux(als_data, gain, integration))
+    }
+
+    pub async fn wait_for_new_sample(&mut self) -> Result<Lux, Error<I::Error>> {
+        // With the F0 persist filter every completed ALS cycle raises the interrupt
+        let apers: register::PersistReg = self.read_register().await?;
+        let persist = apers.persist();
+        let enable: Enable = self.read_register().await?;
+        if persist != Persist::F0 {
+            self.set_persist(Persist::F0).await?;
+        }
+        self.enable_interrupt(true).await?;
+
+        /* Drop any interrupt latched before this call, so the sample is integrated after it.
+         * A stale no-persist interrupt would hold INT low just the same, so clear both.
+         */
+        self.restart_cycle().await?;
+        self.clear_interrupt().await?;
+
+        let result = self.wait_for_threshold_event().await;
+
+        // Put back the persist filter and interrupt enable the caller had set up
+        if persist != Persist::F0 {
+            self.set_persist(persist).await?;
+        }
+        if !enable.aien() {
+            self.enable_interrupt(false).await?;
+        }
+
+        result
+    }
+}
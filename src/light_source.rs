@@ -0,0 +1,105 @@
+use crate::AlsReading;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LightSource {
+    Led,
+    Fluorescent,
+    Daylight,
+    Incandescent,
+    // Too dark to tell
+    Unknown,
+}
+
+impl LightSource {
+    pub fn is_artificial(&self) -> bool {
+        matches!(
+            self,
+            LightSource::Led | LightSource::Fluorescent | LightSource::Incandescent
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Classification {
+    pub source: LightSource,
+
+    /* 100 in the middle of a band, falling to 50 at its edges and 0 for Unknown. The LED and
+     * incandescent bands are open-ended, so they only fall off towards their inner edge.
+     */
+    pub confidence: u8,
+}
+
+/* Upper bounds of each band of the CH1 / CH0 ratio, in thousandths. LEDs emit almost no IR,
+ * fluorescent tubes a little, daylight a fair amount, and incandescent and halogen lamps
+ * put out more IR than visible light.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LightSourceClassifier {
+    pub led_max: u16,
+    pub fluorescent_max: u16,
+    pub daylight_max: u16,
+
+    // CH0 readings below this are too noisy to classify
+    pub min_counts: u16,
+}
+
+impl Default for LightSourceClassifier {
+    fn default() -> Self {
+        LightSourceClassifier {
+            led_max: 60,
+            fluorescent_max: 150,
+            daylight_max: 400,
+            min_counts: 100,
+        }
+    }
+}
+
+impl LightSourceClassifier {
+    pub fn classify(&self, reading: AlsReading) -> Classification {
+        if reading.full_spectrum() < self.min_counts.max(1) {
+            return Classification {
+                source: LightSource::Unknown,
+                confidence: 0,
+            };
+        }
+
+        let ratio = (reading.infrared() as u32 * 1000 / reading.full_spectrum() as u32) as i32;
+        let (led, fluorescent, daylight) = (
+            self.led_max as i32,
+            self.fluorescent_max as i32,
+            self.daylight_max as i32,
+        );
+
+        let (source, confidence) = if ratio < led {
+            // Open-ended at 0, since the less IR the surer it's an LED
+            let confidence = 100 - 50 * ratio / led.max(1);
+            (LightSource::Led, confidence)
+        } else if ratio < fluorescent {
+            (
+                LightSource::Fluorescent,
+                Self::within(ratio, led, fluorescent),
+            )
+        } else if ratio < daylight {
+            (
+                LightSource::Daylight,
+                Self::within(ratio, fluorescent, daylight),
+            )
+        } else {
+            // Open-ended, so grow confidence over the width of the daylight band instead
+            let width = (daylight - fluorescent).max(1);
+            let confidence = 50 + 50 * (ratio - daylight) / width;
+            (LightSource::Incandescent, confidence.min(100))
+        };
+
+        Classification {
+            source,
+            confidence: confidence as u8,
+        }
+    }
+
+    fn within(ratio: i32, low: i32, high: i32) -> i32 {
+        let half_width = ((high - low) / 2).max(1);
+        let distance = (ratio - (low + high) / 2).abs();
+        (100 - 50 * distance / half_width).clamp(50, 100)
+    }
+}
@@ -0,0 +1,203 @@
+use core::cmp::Ordering;
+use core::fmt;
+use core::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+
+/* Fixed-point lux with micro-lux resolution: value = integer + fractional / 1_000_000.
+ * Both components carry the sign of the value, so -1.5 lux is { integer: -1, fractional: -500_000 }.
+ * Comparisons and arithmetic go through the combined micro-lux value, so a pair that doesn't
+ * follow that convention still compares and adds correctly.
+ */
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Lux {
+    // Integer component of lux
+    pub integer: i32,
+
+    // Fractional component of lux (in one-millionth parts)
+    pub fractional: i32,
+}
+
+impl Lux {
+    pub const ZERO: Lux = Lux {
+        integer: 0,
+        fractional: 0,
+    };
+
+    pub const MAX: Lux = Lux {
+        integer: i32::MAX,
+        fractional: 999_999,
+    };
+
+    pub const MIN: Lux = Lux {
+        integer: i32::MIN,
+        fractional: -999_999,
+    };
+
+    // Saturates at Lux::MIN / Lux::MAX, since the integer part is only an i32
+    pub const fn from_micro_lux(micro_lux: i64) -> Lux {
+        if micro_lux > Self::MAX.micro_lux() {
+            return Self::MAX;
+        }
+        if micro_lux < Self::MIN.micro_lux() {
+            return Self::MIN;
+        }
+
+        Lux {
+            integer: (micro_lux / 1_000_000) as i32,
+            fractional: (micro_lux % 1_000_000) as i32,
+        }
+    }
+
+    pub const fn from_milli_lux(milli_lux: i64) -> Lux {
+        Self::from_micro_lux(milli_lux.saturating_mul(1000))
+    }
+
+    pub const fn micro_lux(&self) -> i64 {
+        self.integer as i64 * 1_000_000 + self.fractional as i64
+    }
+
+    // Truncates towards zero, like integer division
+    pub const fn milli_lux(&self) -> i64 {
+        self.micro_lux() / 1000
+    }
+
+    // Negative readings (e.g. from a calibration offset) clamp to 0
+    pub fn milli_lux_u32(&self) -> u32 {
+        self.milli_lux().clamp(0, u32::MAX as i64) as u32
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.micro_lux() < 0
+    }
+
+    // Multiplies by numerator / denominator without going through floating point
+    pub fn scale(self, numerator: i64, denominator: i64) -> Lux {
+        let micro_lux = self.micro_lux() as i128 * numerator as i128 / denominator as i128;
+        Lux::from_micro_lux(micro_lux.clamp(i64::MIN as i128, i64::MAX as i128) as i64)
+    }
+}
+
+impl PartialEq for Lux {
+    fn eq(&self, other: &Self) -> bool {
+        self.micro_lux() == other.micro_lux()
+    }
+}
+
+impl Eq for Lux {}
+
+impl PartialOrd for Lux {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Lux {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.micro_lux().cmp(&other.micro_lux())
+    }
+}
+
+impl Add for Lux {
+    type Output = Lux;
+
+    fn add(self, rhs: Lux) -> Lux {
+        Lux::from_micro_lux(self.micro_lux() + rhs.micro_lux())
+    }
+}
+
+impl AddAssign for Lux {
+    fn add_assign(&mut self, rhs: Lux) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub for Lux {
+    type Output = Lux;
+
+    fn sub(self, rhs: Lux) -> Lux {
+        Lux::from_micro_lux(self.micro_lux() - rhs.micro_lux())
+    }
+}
+
+impl SubAssign for Lux {
+    fn sub_assign(&mut self, rhs: Lux) {
+        *self = *self - rhs;
+    }
+}
+
+impl Neg for Lux {
+    type Output = Lux;
+
+    fn neg(self) -> Lux {
+        Lux::from_micro_lux(-self.micro_lux())
+    }
+}
+
+impl Mul<i32> for Lux {
+    type Output = Lux;
+
+    fn mul(self, rhs: i32) -> Lux {
+        self.scale(rhs as i64, 1)
+    }
+}
+
+impl Div<i32> for Lux {
+    type Output = Lux;
+
+    fn div(self, rhs: i32) -> Lux {
+        Lux::from_micro_lux(self.micro_lux() / rhs as i64)
+    }
+}
+
+/* Prints with the formatter's precision (e.g. `{:.2}`), rounded half away from zero.
+ * Without one, all six decimal places are printed.
+ */
+impl fmt::Display for Lux {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let decimals = f.precision().unwrap_or(6).min(6) as u32;
+        let step = 10u64.pow(6 - decimals);
+
+        let micro_lux = self.micro_lux();
+        let rounded = (micro_lux.unsigned_abs() + step / 2) / step;
+        let unit = 10u64.pow(decimals);
+
+        let sign = if micro_lux < 0 && rounded != 0 {
+            "-"
+        } else {
+            ""
+        };
+        write!(f, "{}{}", sign, rounded / unit)?;
+        if decimals > 0 {
+            write!(f, ".{:0width$}", rounded % unit, width = decimals as usize)?;
+        }
+
+        Ok(())
+    }
+}
+
+// Keeps the integer part exact, f32 alone can't hold micro-lux above ~16 lux
+#[cfg(feature = "float")]
+#[duplicate::duplicate_item(float; [f32]; [f64];)]
+impl From<Lux> for float {
+    fn from(lux: Lux) -> float {
+        lux.integer as float + lux.fractional as float / 1_000_000.0
+    }
+}
+
+#[cfg(feature = "float")]
+#[duplicate::duplicate_item(
+    from_float float;
+    [from_f32] [f32];
+    [from_f64] [f64];
+)]
+impl Lux {
+    // Rounds to the nearest micro-lux, saturating at Lux::MIN / Lux::MAX
+    pub fn from_float(lux: float) -> Lux {
+        let micro_lux = lux * 1_000_000.0;
+        let rounded = if micro_lux < 0.0 {
+            micro_lux - 0.5
+        } else {
+            micro_lux + 0.5
+        };
+        Lux::from_micro_lux(rounded as i64)
+    }
+}
@@ -0,0 +1,216 @@
+#[cfg(feature = "float")]
+use duplicate::duplicate_item;
+
+use crate::{chip, AlsReading, Gain, Integration, Lux};
+
+// Converts a pair of raw channel counts into lux for the gain and integration time they were read at
+pub trait LuxModel {
+    fn lux(&self, reading: AlsReading, gain: Gain, integration: Integration) -> Lux;
+
+    // Floating-point versions default to converting the fixed-point result
+    #[cfg(feature = "float")]
+    fn lux_f32(&self, reading: AlsReading, gain: Gain, integration: Integration) -> f32 {
+        self.lux(reading, gain, integration).into()
+    }
+
+    #[cfg(feature = "float")]
+    fn lux_f64(&self, reading: AlsReading, gain: Gain, integration: Integration) -> f64 {
+        self.lux(reading, gain, integration).into()
+    }
+}
+
+// Widened to i128 so custom coefficients can't overflow; anything past Lux::MAX saturates
+fn lux_from_ratio(numerator: i128, denominator: i128) -> Lux {
+    let micro_lux = numerator * 1_000_000 / denominator;
+    Lux::from_micro_lux(micro_lux.clamp(i64::MIN as i128, i64::MAX as i128) as i64)
+}
+
+/* lux = (CH0 - CH1) * (1 - CH1 / CH0) / CPL, where CPL = ATIME * AGAIN / DF.
+ * Kept in one place since the default and Adafruit models only differ in their gain figures.
+ */
+fn ir_ratio_lux(reading: AlsReading, cpl: i64) -> Lux {
+    let ch0 = reading.full_spectrum() as i64;
+    let ch1 = reading.infrared() as i64;
+
+    /* Avoided using floating point math just in case architecture does not support it.
+     * When noise pushes CH1 above CH0 both factors go negative, so use the saturated visible
+     * count rather than let them multiply out to a bogus positive reading.
+     */
+    let strength = if ch0 > 0 {
+        reading.visible() as i64 * (1_000_000 - (ch1 * 1_000_000) / ch0) * chip::LUX_DF as i64
+    } else {
+        0
+    };
+
+    lux_from_ratio(strength as i128, cpl as i128 * 1_000_000)
+}
+
+#[cfg(feature = "float")]
+#[duplicate_item(
+    ir_ratio_lux_float float;
+    [ir_ratio_lux_f32] [f32];
+    [ir_ratio_lux_f64] [f64];
+)]
+fn ir_ratio_lux_float(reading: AlsReading, cpl: float) -> float {
+    let ch0 = reading.full_spectrum() as float;
+    let ch1 = reading.infrared() as float;
+
+    if ch0 > 0.0 {
+        let cpl = cpl / chip::LUX_DF as float;
+        reading.visible() as float * (1.0 - ch1 / ch0) / cpl
+    } else {
+        0.0
+    }
+}
+
+// The formula this driver has always used, with the nominal 1x/25x/400x/9200x gains
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DefaultLux;
+
+impl LuxModel for DefaultLux {
+    fn lux(&self, reading: AlsReading, gain: Gain, integration: Integration) -> Lux {
+        let cpl = integration.millis() as i64 * gain.multiplier() as i64;
+        ir_ratio_lux(reading, cpl)
+    }
+
+    #[cfg(feature = "float")]
+    #[duplicate_item(
+        lux_float ir_ratio_lux_float float;
+        [lux_f32] [ir_ratio_lux_f32] [f32];
+        [lux_f64] [ir_ratio_lux_f64] [f64];
+    )]
+    fn lux_float(&self, reading: AlsReading, gain: Gain, integration: Integration) -> float {
+        let cpl = integration.millis() as float * gain.multiplier() as float;
+        ir_ratio_lux_float(reading, cpl)
+    }
+}
+
+// Same formula as the Adafruit library, which uses the typical 428x/9876x datasheet gains
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AdafruitLux;
+
+impl AdafruitLux {
+    fn multiplier(gain: Gain) -> i64 {
+        match gain {
+            Gain::Low => 1,
+            Gain::Med => 25,
+            Gain::High => 428,
+            Gain::Max => 9876,
+        }
+    }
+}
+
+impl LuxModel for AdafruitLux {
+    fn lux(&self, reading: AlsReading, gain: Gain, integration: Integration) -> Lux {
+        let cpl = integration.millis() as i64 * Self::multiplier(gain);
+        ir_ratio_lux(reading, cpl)
+    }
+
+    #[cfg(feature = "float")]
+    #[duplicate_item(
+        lux_float ir_ratio_lux_float float;
+        [lux_f32] [ir_ratio_lux_f32] [f32];
+        [lux_f64] [ir_ratio_lux_f64] [f64];
+    )]
+    fn lux_float(&self, reading: AlsReading, gain: Gain, integration: Integration) -> float {
+        let cpl = integration.millis() as float * Self::multiplier(gain) as float;
+        ir_ratio_lux_float(reading, cpl)
+    }
+}
+
+/* Two-segment piecewise fit from ams design note DN40:
+ *   lux = max(CH0 - B * CH1, C * CH0 - D * CH1) / CPL, where CPL = ATIME * AGAIN / DF
+ * B, C and D are in thousandths, so custom coefficients (e.g. loaded from a calibration in
+ * flash) stay integer-only. Hand them to the driver with with_lux_model / set_lux_model.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Dn40Lux {
+    pub device_factor: u32,
+    pub coef_b: u32,
+    pub coef_c: u32,
+    pub coef_d: u32,
+}
+
+impl Dn40Lux {
+    // Coefficients published for the TSL2591 in open air
+    pub const TSL2591: Dn40Lux = Dn40Lux {
+        device_factor: 408,
+        coef_b: 1640,
+        coef_c: 590,
+        coef_d: 860,
+    };
+}
+
+impl Default for Dn40Lux {
+    fn default() -> Self {
+        Self::TSL2591
+    }
+}
+
+impl LuxModel for Dn40Lux {
+    fn lux(&self, reading: AlsReading, gain: Gain, integration: Integration) -> Lux {
+        let ch0 = reading.full_spectrum() as i128;
+        let ch1 = reading.infrared() as i128;
+
+        let lux1 = ch0 * 1000 - self.coef_b as i128 * ch1;
+        let lux2 = self.coef_c as i128 * ch0 - self.coef_d as i128 * ch1;
+        let strength = lux1.max(lux2).max(0) * self.device_factor as i128;
+
+        let cpl = integration.millis() as i128 * gain.multiplier() as i128;
+        lux_from_ratio(strength, cpl * 1000)
+    }
+
+    #[cfg(feature = "float")]
+    #[duplicate_item(
+        lux_float float;
+        [lux_f32] [f32];
+        [lux_f64] [f64];
+    )]
+    fn lux_float(&self, reading: AlsReading, gain: Gain, integration: Integration) -> float {
+        let ch0 = reading.full_spectrum() as float;
+        let ch1 = reading.infrared() as float;
+        let (coef_b, coef_c, coef_d) = (
+            self.coef_b as float / 1000.0,
+            self.coef_c as float / 1000.0,
+            self.coef_d as float / 1000.0,
+        );
+
+        let lux1 = ch0 - coef_b * ch1;
+        let lux2 = coef_c * ch0 - coef_d * ch1;
+        let cpl = integration.millis() as float * gain.multiplier() as float
+            / self.device_factor as float;
+        lux1.max(lux2).max(0.0) / cpl
+    }
+}
+
+/* Largest CH0 count (with no IR) that the model maps to at most the given lux, found by
+ * bisection so it works for any model that is monotonic in CH0. IR lowers the lux computed
+ * for a given CH0, so under IR-rich light thresholds trip at a somewhat lower lux than requested.
+ */
+pub(crate) fn counts_for<M: LuxModel>(
+    model: &M,
+    micro_lux: i64,
+    gain: Gain,
+    integration: Integration,
+) -> u16 {
+    let micro = |ch0: u16| {
+        model
+            .lux(AlsReading::new(ch0, 0), gain, integration)
+            .micro_lux()
+    };
+
+    let (mut low, mut high) = (0u32, u16::MAX as u32);
+    if micro(0) > micro_lux {
+        return 0;
+    }
+    while low < high {
+        let mid = (low + high).div_ceil(2);
+        if micro(mid as u16) <= micro_lux {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+
+    low as u16
+}
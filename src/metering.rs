@@ -0,0 +1,164 @@
+use crate::Lux;
+
+/* Incident light metering: EV100 = log2(E * 100 / C), and N² / t = 2^EV at the ISO in use.
+ * Logarithms are done in Q16 fixed point so none of this needs floating point.
+ */
+const Q16: i64 = 1 << 16;
+const Q30: u64 = 1 << 30;
+
+// 2^(2^-i) in Q30 for i = 1..=16, used to build up fractional powers of two
+const EXP2_FRACTION: [u64; 16] = [
+    1518500250, 1276901417, 1170923762, 1121280436, 1097253708, 1085434106, 1079572136, 1076653033,
+    1075196443, 1074468888, 1074105294, 1073923544, 1073832680, 1073787251, 1073764537, 1073753181,
+];
+
+// Full-stop apertures in tenths of an f-number, from f/1.0 to f/22
+const STANDARD_APERTURES: [u16; 10] = [10, 14, 20, 28, 40, 56, 80, 110, 160, 220];
+
+// log2(0) is undefined, so zero is treated as one rather than poisoning the arithmetic
+fn log2_q16(x: u64) -> i64 {
+    let x = x.max(1);
+    let exponent = 63 - x.leading_zeros() as i64;
+    let mut y = if exponent > 30 {
+        x >> (exponent - 30)
+    } else {
+        x << (30 - exponent)
+    };
+
+    // Squaring doubles the exponent, so each overflow past 2.0 yields the next fraction bit
+    let mut fraction = 0;
+    for _ in 0..16 {
+        y = (y * y) >> 30;
+        fraction <<= 1;
+        if y >= 2 * Q30 {
+            y >>= 1;
+            fraction |= 1;
+        }
+    }
+
+    exponent * Q16 + fraction
+}
+
+fn exp2_q16(x: i64) -> u64 {
+    let exponent = x >> 16;
+    let fraction = x & (Q16 - 1);
+
+    let mut y = Q30;
+    for (bit, factor) in EXP2_FRACTION.iter().enumerate() {
+        if fraction & (1 << (15 - bit)) != 0 {
+            y = (y * factor) >> 30;
+        }
+    }
+
+    match exponent - 30 {
+        shift if shift >= 34 => u64::MAX,
+        shift if shift >= 0 => y << shift,
+        shift if shift > -64 => (y + (1 << (-shift - 1))) >> -shift,
+        _ => 0,
+    }
+}
+
+// Exposure value in hundredths of a stop, e.g. Ev(1250) is EV 12.5
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Ev(pub i32);
+
+impl Ev {
+    fn q16(self) -> i64 {
+        self.0 as i64 * Q16 / 100
+    }
+
+    fn from_q16(q16: i64) -> Ev {
+        // Round to the nearest hundredth rather than towards zero
+        Ev(((q16 * 100 + q16.signum() * Q16 / 2) / Q16) as i32)
+    }
+}
+
+/* One suggested camera setting. Aperture is in tenths of an f-number (28 is f/2.8) and
+ * shutter time is in microseconds, saturating at u32::MAX for very long exposures.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Exposure {
+    pub iso: u32,
+    pub aperture: u16,
+    pub shutter_us: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LightMeter {
+    // Incident-meter calibration constant C, 250 for most flat and dome receptors
+    pub calibration_constant: u16,
+}
+
+impl Default for LightMeter {
+    fn default() -> Self {
+        LightMeter {
+            calibration_constant: 250,
+        }
+    }
+}
+
+impl LightMeter {
+    // Returns None in complete darkness, where EV is undefined, or if calibration_constant is 0
+    pub fn ev100(&self, lux: Lux) -> Option<Ev> {
+        if self.calibration_constant == 0 {
+            return None;
+        }
+        let micro_lux = u64::try_from(lux.micro_lux()).ok().filter(|&l| l > 0)?;
+
+        let ev = log2_q16(micro_lux * 100) - log2_q16(self.calibration_constant as u64 * 1_000_000);
+        Some(Ev::from_q16(ev))
+    }
+
+    // EV at the given ISO, i.e. EV100 + log2(ISO / 100)
+    fn ev_at_iso(ev100: Ev, iso: u32) -> i64 {
+        ev100.q16() + log2_q16(iso as u64) - log2_q16(100)
+    }
+
+    // t = N² / 2^EV
+    pub fn shutter_for(&self, ev100: Ev, iso: u32, aperture: u16) -> Exposure {
+        let log_t = 2 * log2_q16(aperture as u64) - log2_q16(100) + log2_q16(1_000_000)
+            - Self::ev_at_iso(ev100, iso);
+
+        Exposure {
+            iso,
+            aperture,
+            shutter_us: exp2_q16(log_t).min(u32::MAX as u64) as u32,
+        }
+    }
+
+    // N = sqrt(t * 2^EV)
+    pub fn aperture_for(&self, ev100: Ev, iso: u32, shutter_us: u32) -> Exposure {
+        let log_n = (log2_q16(shutter_us as u64) - log2_q16(1_000_000)
+            + Self::ev_at_iso(ev100, iso)
+            + log2_q16(100))
+            / 2;
+
+        Exposure {
+            iso,
+            aperture: exp2_q16(log_n).min(u16::MAX as u64) as u16,
+            shutter_us,
+        }
+    }
+
+    // ISO = 100 * N² / (t * 2^EV100)
+    pub fn iso_for(&self, ev100: Ev, aperture: u16, shutter_us: u32) -> Exposure {
+        let log_iso = 2 * log2_q16(aperture as u64) - log2_q16(100) + log2_q16(1_000_000)
+            - log2_q16(shutter_us as u64)
+            - ev100.q16()
+            + log2_q16(100);
+
+        Exposure {
+            iso: exp2_q16(log_iso).min(u32::MAX as u64) as u32,
+            aperture,
+            shutter_us,
+        }
+    }
+
+    // Shutter times for each full-stop aperture from f/1.0 to f/22 at the given ISO
+    pub fn suggestions(&self, ev100: Ev, iso: u32) -> impl Iterator<Item = Exposure> {
+        let meter = *self;
+        STANDARD_APERTURES
+            .iter()
+            .map(move |&aperture| meter.shutter_for(ev100, iso, aperture))
+    }
+}
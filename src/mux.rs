@@ -0,0 +1,258 @@
+/* TCA9548A I2C multiplexer support. Every TSL2591 answers on the same fixed address, so
+ * arrays of them sit behind a mux, one sensor per downstream channel.
+ */
+use core::cell::Cell;
+use core::future::poll_fn;
+use core::ops::{Deref, DerefMut};
+use core::task::{Poll, Waker};
+
+use duplicate::{duplicate, duplicate_item};
+use embedded_hal::i2c::{ErrorType, Operation};
+
+use crate::{AlsReading, DefaultLux, Error, Lux, LuxModel, NoIntPin, Tsl2591, Tsl2591Async};
+
+// Default address with A0..A2 tied low, strapping them adds up to 7
+pub const TCA9548A_ADDR: u8 = 0x70;
+
+pub const CHANNELS: u8 = 8;
+
+/* Owns the upstream bus and hands out one I2C device per channel. The channel is only
+ * re-selected when a different channel than last time is used, so a sensor polled on its
+ * own costs no extra bus traffic.
+ *
+ * The channel select and the transaction behind it have to go out back to back, so the bus is
+ * taken out for the whole transaction. Async transactions on other channels park their waker
+ * and are woken once it is handed back.
+ */
+pub struct Tca9548a<I> {
+    i2c: Cell<Option<I>>,
+    address: u8,
+    selected: Cell<Option<u8>>,
+
+    // Tasks waiting for the bus, one slot per channel covers an array with a sensor on each
+    wakers: [Cell<Option<Waker>>; CHANNELS as usize],
+}
+
+impl<I> Tca9548a<I> {
+    pub fn new(i2c: I) -> Self {
+        Self::with_address(i2c, TCA9548A_ADDR)
+    }
+
+    pub fn with_address(i2c: I, address: u8) -> Self {
+        Tca9548a {
+            i2c: Cell::new(Some(i2c)),
+            address,
+            selected: Cell::new(None),
+            wakers: Default::default(),
+        }
+    }
+
+    // Panics if the channel is out of range, as that is a wiring mistake rather than a bus error
+    pub fn channel(&self, channel: u8) -> MuxChannel<'_, I> {
+        assert!(channel < CHANNELS, "TCA9548A only has 8 channels");
+        MuxChannel { mux: self, channel }
+    }
+
+    pub fn destroy(self) -> I {
+        // Taking self means no transaction can be holding on to the bus
+        self.i2c
+            .into_inner()
+            .expect("TCA9548A bus was not handed back")
+    }
+
+    // A blocking transaction can only find the bus gone if it was started mid async transaction
+    fn bus(&self) -> Bus<'_, I> {
+        let i2c = self
+            .i2c
+            .take()
+            .expect("TCA9548A bus is in use by an async transaction");
+        Bus {
+            mux: self,
+            i2c: Some(i2c),
+        }
+    }
+
+    async fn bus_async(&self) -> Bus<'_, I> {
+        poll_fn(|cx| match self.i2c.take() {
+            Some(i2c) => Poll::Ready(Bus {
+                mux: self,
+                i2c: Some(i2c),
+            }),
+            None => {
+                self.park(cx.waker());
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    fn park(&self, waker: &Waker) {
+        for slot in &self.wakers {
+            match slot.take() {
+                Some(parked) if !parked.will_wake(waker) => slot.set(Some(parked)),
+                _ => {
+                    slot.set(Some(waker.clone()));
+                    return;
+                }
+            }
+        }
+
+        // More waiting tasks than slots, so fall back to polling again straight away
+        waker.wake_by_ref();
+    }
+}
+
+// The bus taken out of the mux, handed back on drop even if the transaction is cancelled
+struct Bus<'a, I> {
+    mux: &'a Tca9548a<I>,
+    i2c: Option<I>,
+}
+
+impl<I> Deref for Bus<'_, I> {
+    type Target = I;
+
+    fn deref(&self) -> &I {
+        self.i2c.as_ref().unwrap()
+    }
+}
+
+impl<I> DerefMut for Bus<'_, I> {
+    fn deref_mut(&mut self) -> &mut I {
+        self.i2c.as_mut().unwrap()
+    }
+}
+
+impl<I> Drop for Bus<'_, I> {
+    fn drop(&mut self) {
+        self.mux.i2c.set(self.i2c.take());
+
+        // Every waiter gets a go, whichever loses the race parks itself again
+        for slot in &self.mux.wakers {
+            if let Some(waker) = slot.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+// One downstream channel of the mux, usable wherever an I2C bus is expected
+pub struct MuxChannel<'a, I> {
+    mux: &'a Tca9548a<I>,
+    channel: u8,
+}
+
+impl<I: ErrorType> ErrorType for MuxChannel<'_, I> {
+    type Error = I::Error;
+}
+
+#[duplicate_item(
+    _hal_ async add_await(code) bus;
+    [embedded_hal] [] [code] [bus];
+    [embedded_hal_async] [async] [code.await] [bus_async];
+)]
+impl<I: _hal_::i2c::I2c> _hal_::i2c::I2c for MuxChannel<'_, I> {
+    async fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        let mut i2c = add_await([self.mux.bus()]);
+
+        if self.mux.selected.get() != Some(self.channel) {
+            // Forget the selection first, in case the write fails part way
+            self.mux.selected.set(None);
+            add_await([i2c.write(self.mux.address, &[1 << self.channel])])?;
+            self.mux.selected.set(Some(self.channel));
+        }
+
+        add_await([i2c.transaction(address, operations)])
+    }
+}
+
+duplicate! {
+    [
+        _array_ _sensor_(model);
+        [Tsl2591Array] [Tsl2591<MuxChannel<'a, I>, model>];
+        [Tsl2591AsyncArray] [Tsl2591Async<MuxChannel<'a, I>, NoIntPin, model>];
+    ]
+    // N sensors behind one mux, in the order their channels were given
+    pub struct _array_<'a, I, const N: usize, M = DefaultLux> {
+        sensors: [_sensor_([M]); N],
+        channels: [u8; N],
+    }
+}
+
+#[duplicate_item(
+    _array_ _tsl2591_ _hal_ async add_await(code);
+    [Tsl2591Array] [Tsl2591] [embedded_hal] [] [code];
+    [Tsl2591AsyncArray] [Tsl2591Async] [embedded_hal_async] [async] [code.await];
+)]
+impl<'a, I: _hal_::i2c::I2c, const N: usize> _array_<'a, I, N> {
+    // On failure, returns the index of the first sensor that couldn't be initialised
+    pub async fn new(
+        mux: &'a Tca9548a<I>,
+        channels: [u8; N],
+    ) -> Result<Self, (usize, Error<I::Error>)> {
+        let mut sensors: [Option<_tsl2591_<MuxChannel<'a, I>>>; N] = core::array::from_fn(|_| None);
+        for (index, sensor) in sensors.iter_mut().enumerate() {
+            let tsl2591 = add_await([_tsl2591_::new(mux.channel(channels[index]))]);
+            *sensor = Some(tsl2591.map_err(|error| (index, error))?);
+        }
+
+        Ok(_array_ {
+            sensors: sensors.map(|sensor| sensor.unwrap()),
+            channels,
+        })
+    }
+}
+
+#[duplicate_item(
+    _array_ _sensor_(model) _hal_ async add_await(code);
+    [Tsl2591Array] [Tsl2591<MuxChannel<'a, I>, model>] [embedded_hal] [] [code];
+    [Tsl2591AsyncArray] [Tsl2591Async<MuxChannel<'a, I>, NoIntPin, model>] [embedded_hal_async]
+        [async] [code.await];
+)]
+impl<'a, I: _hal_::i2c::I2c, const N: usize, M: LuxModel> _array_<'a, I, N, M> {
+    // Gives every sensor its own copy of the model
+    pub fn with_lux_model<L: LuxModel + Clone>(self, lux_model: L) -> _array_<'a, I, N, L> {
+        _array_ {
+            sensors: self
+                .sensors
+                .map(|sensor| sensor.with_lux_model(lux_model.clone())),
+            channels: self.channels,
+        }
+    }
+
+    pub fn channels(&self) -> &[u8; N] {
+        &self.channels
+    }
+
+    pub fn sensors(&mut self) -> &mut [_sensor_([M]); N] {
+        &mut self.sensors
+    }
+
+    pub fn sensor(&mut self, index: usize) -> Option<&mut _sensor_([M])> {
+        self.sensors.get_mut(index)
+    }
+    pub async fn read_all_raw(
+        &mut self,
+        check_complete: bool,
+    ) -> [Result<AlsReading, Error<I::Error>>; N] {
+        let mut readings = core::array::from_fn(|_| Err(Error::CycleIncomplete));
+        for (reading, sensor) in readings.iter_mut().zip(self.sensors.iter_mut()) {
+            *reading = add_await([sensor.get_raw_als_data(check_complete)]);
+        }
+
+        readings
+    }
+
+    // One reading per sensor, a failing sensor doesn't stop the others being read
+    pub async fn read_all(&mut self, check_complete: bool) -> [Result<Lux, Error<I::Error>>; N] {
+        let mut readings = core::array::from_fn(|_| Err(Error::CycleIncomplete));
+        for (reading, sensor) in readings.iter_mut().zip(self.sensors.iter_mut()) {
+            *reading = add_await([sensor.get_lux(check_complete)]);
+        }
+
+        readings
+    }
+}
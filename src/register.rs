@@ -0,0 +1,171 @@
+/* Raw register contents. Registers whose decoded form lives at the crate root (Config, Persist
+ * and Status) carry a Reg suffix so the two can't be mixed up on import.
+ */
+use bitfield::bitfield;
+
+use crate::{chip, Gain, Integration};
+
+// Only the registers defined here can be accessed, so reserved bits can't be written by accident
+mod sealed {
+    pub trait Sealed {}
+}
+
+// Registers that can be read from the chip with `read_register`
+pub trait Register: Copy + sealed::Sealed {
+    const ADDR: u8;
+
+    fn from_bits(bits: u8) -> Self;
+    fn bits(&self) -> u8;
+}
+
+// Registers that can also be written with `write_register` and `modify_register`
+pub trait WritableRegister: Register {
+    // Reserved bits are cleared before the value is written to the chip
+    const WRITE_MASK: u8;
+}
+
+macro_rules! register {
+    ($name:ident, $addr:expr) => {
+        impl sealed::Sealed for $name {}
+
+        impl Register for $name {
+            const ADDR: u8 = $addr;
+
+            fn from_bits(bits: u8) -> Self {
+                $name(bits)
+            }
+
+            fn bits(&self) -> u8 {
+                self.0
+            }
+        }
+    };
+    ($name:ident, $addr:expr, $write_mask:expr) => {
+        register!($name, $addr);
+
+        impl WritableRegister for $name {
+            const WRITE_MASK: u8 = $write_mask;
+        }
+    };
+}
+
+bitfield! {
+    /* Enable: (0x00): NPIEN:7 | SAI:6 | Reserved:5 | AIEN:4 | Reserved:3:2 | AEN:1 | PON:0 */
+    #[derive(Clone, Copy, Default, PartialEq, Eq)]
+    pub struct Enable(u8);
+    impl Debug;
+    pub npien, set_npien: 7;
+    pub sai, set_sai: 6;
+    pub aien, set_aien: 4;
+    pub aen, set_aen: 1;
+    pub pon, set_pon: 0;
+}
+register!(Enable, chip::reg::ENABLE, 0b1101_0011);
+
+bitfield! {
+    /* Config/Control: (0x01): SRESET:7 | Reserved:6 | AGAIN:5:4 | Reserved:3 | ATIME:2:0 */
+    #[derive(Clone, Copy, Default, PartialEq, Eq)]
+    pub struct ControlReg(u8);
+    impl Debug;
+    pub sreset, set_sreset: 7;
+    pub u8, again, set_again: 5, 4;
+    pub u8, atime, set_atime: 2, 0;
+}
+register!(ControlReg, chip::reg::CONFIG, 0b1011_0111);
+
+impl ControlReg {
+    pub fn gain(&self) -> Gain {
+        match self.again() {
+            0 => Gain::Low,
+            1 => Gain::Med,
+            2 => Gain::High,
+            _ => Gain::Max,
+        }
+    }
+
+    pub fn set_gain(&mut self, gain: Gain) {
+        self.set_again(gain as u8 >> 4);
+    }
+
+    // ATIME values 0b110 and 0b111 are not defined by the datasheet
+    pub fn integration(&self) -> Option<Integration> {
+        match self.atime() {
+            0 => Some(Integration::T100ms),
+            1 => Some(Integration::T200ms),
+            2 => Some(Integration::T300ms),
+            3 => Some(Integration::T400ms),
+            4 => Some(Integration::T500ms),
+            5 => Some(Integration::T600ms),
+            _ => None,
+        }
+    }
+
+    pub fn set_integration(&mut self, time: Integration) {
+        self.set_atime(time as u8);
+    }
+}
+
+bitfield! {
+    /* Persist: (0x0C): Reserved:7:4 | APERS:3:0 */
+    #[derive(Clone, Copy, Default, PartialEq, Eq)]
+    pub struct PersistReg(u8);
+    impl Debug;
+    pub u8, apers, set_apers: 3, 0;
+}
+register!(PersistReg, chip::reg::PERSIST, 0b0000_1111);
+
+impl PersistReg {
+    pub fn persist(&self) -> crate::Persist {
+        match self.apers() {
+            0x00 => crate::Persist::F0,
+            0x01 => crate::Persist::F1,
+            0x02 => crate::Persist::F2,
+            0x03 => crate::Persist::F3,
+            0x04 => crate::Persist::F5,
+            0x05 => crate::Persist::F10,
+            0x06 => crate::Persist::F15,
+            0x07 => crate::Persist::F20,
+            0x08 => crate::Persist::F25,
+            0x09 => crate::Persist::F30,
+            0x0A => crate::Persist::F35,
+            0x0B => crate::Persist::F40,
+            0x0C => crate::Persist::F45,
+            0x0D => crate::Persist::F50,
+            0x0E => crate::Persist::F55,
+            _ => crate::Persist::F60,
+        }
+    }
+
+    pub fn set_persist(&mut self, persist: crate::Persist) {
+        self.set_apers(persist as u8);
+    }
+}
+
+bitfield! {
+    /* PID: (0x11): Reserved:7:6 | PID:5:4 | Reserved:3:0 */
+    #[derive(Clone, Copy, Default, PartialEq, Eq)]
+    pub struct Pid(u8);
+    impl Debug;
+    pub u8, pid, _: 5, 4;
+}
+register!(Pid, chip::reg::PID);
+
+bitfield! {
+    /* ID: (0x12): ID:7:0 */
+    #[derive(Clone, Copy, Default, PartialEq, Eq)]
+    pub struct Id(u8);
+    impl Debug;
+    pub u8, id, _: 7, 0;
+}
+register!(Id, chip::reg::ID);
+
+bitfield! {
+    /* Status: (0x13): Reserved:7:6 | NPINTR:5 | AINT:4 | Reserved:3:1 | AVALID:0 */
+    #[derive(Clone, Copy, Default, PartialEq, Eq)]
+    pub struct StatusReg(u8);
+    impl Debug;
+    pub npintr, _: 5;
+    pub aint, _: 4;
+    pub avalid, _: 0;
+}
+register!(StatusReg, chip::reg::STATUS);
@@ -0,0 +1,319 @@
+/* Typestate wrappers around the drivers, so reading while the ADC is off is a compile error.
+ * A PoweredOff handle only exposes configuration and power_on(); power transitions consume
+ * the handle and hand it back alongside the error if the I2C write fails.
+ */
+use core::marker::PhantomData;
+
+use duplicate::{duplicate, duplicate_item};
+
+use crate::register::Register;
+use crate::{
+    AlsReading, AutoRange, Calibration, Config, DefaultLux, Error, Gain, Integration, Irradiance,
+    Lux, LuxModel, NoIntPin, Persist, SpecialFunction, Status, TrackingWindow,
+};
+
+pub struct PoweredOn;
+pub struct PoweredOff;
+
+mod sealed {
+    pub trait Sealed {}
+
+    impl Sealed for super::PoweredOn {}
+    impl Sealed for super::PoweredOff {}
+}
+
+pub trait PowerState: sealed::Sealed {
+    const POWERED_ON: bool;
+}
+
+impl PowerState for PoweredOn {
+    const POWERED_ON: bool = true;
+}
+
+impl PowerState for PoweredOff {
+    const POWERED_ON: bool = false;
+}
+
+// Result of a power transition, which gives the original handle back if it fails
+pub type Transition<Next, Current, E> = Result<Next, (Current, Error<E>)>;
+
+duplicate! {
+    [
+        _tsl2591_ _generics_ _inner_;
+        [Tsl2591] [I, S, M = DefaultLux] [crate::Tsl2591<I, M>];
+        [Tsl2591Async] [I, S, P = NoIntPin, M = DefaultLux] [crate::Tsl2591Async<I, P, M>];
+    ]
+    pub struct _tsl2591_<_generics_> {
+        inner: _inner_,
+        state: PhantomData<S>,
+    }
+}
+
+#[duplicate_item(
+    _tsl2591_ _hal_ async add_await(code);
+    [Tsl2591] [embedded_hal] [] [code];
+    [Tsl2591Async] [embedded_hal_async] [async] [code.await];
+)]
+impl<I: _hal_::i2c::I2c> _tsl2591_<I, PoweredOn> {
+    pub async fn new(i2c: I) -> Result<Self, Error<I::Error>> {
+        let inner = add_await([crate::_tsl2591_::new(i2c)])?;
+        Ok(Self {
+            inner,
+            state: PhantomData,
+        })
+    }
+
+    pub async fn with_config(i2c: I, config: Config) -> Result<Self, Error<I::Error>> {
+        let config = config.with_powered_on(true);
+        let inner = add_await([crate::_tsl2591_::with_config(i2c, config)])?;
+        Ok(Self {
+            inner,
+            state: PhantomData,
+        })
+    }
+}
+
+#[duplicate_item(
+    _tsl2591_ async add_await(code) _generics_ _args_ _with_state_(state) _with_model_(model)
+        _inner_;
+    [Tsl2591] [] [code] [I: embedded_hal::i2c::I2c, S: PowerState, M: LuxModel] [I, S, M]
+        [I, state, M] [I, S, model] [crate::Tsl2591<I, M>];
+    [Tsl2591Async] [async] [code.await]
+        [I: embedded_hal_async::i2c::I2c, S: PowerState, P, M: LuxModel] [I, S, P, M]
+        [I, state, P, M] [I, S, P, model] [crate::Tsl2591Async<I, P, M>];
+)]
+impl<_generics_> _tsl2591_<_args_> {
+    fn into_state<T>(self) -> _tsl2591_<_with_state_([T])> {
+        _tsl2591_ {
+            inner: self.inner,
+            state: PhantomData,
+        }
+    }
+
+    pub fn with_lux_model<N: LuxModel>(self, lux_model: N) -> _tsl2591_<_with_model_([N])> {
+        _tsl2591_ {
+            inner: self.inner.with_lux_model(lux_model),
+            state: PhantomData,
+        }
+    }
+
+    // Hands back the plain driver, e.g. to use features this wrapper doesn't expose
+    pub fn release(self) -> _inner_ {
+        self.inner
+    }
+
+    pub async fn get_id(&mut self) -> Result<u8, Error<I::Error>> {
+        add_await([self.inner.get_id()])
+    }
+
+    pub async fn read_register<R: Register>(&mut self) -> Result<R, Error<I::Error>> {
+        add_await([self.inner.read_register()])
+    }
+
+    pub async fn get_config(&mut self) -> Result<Config, Error<I::Error>> {
+        add_await([self.inner.get_config()])
+    }
+
+    pub async fn get_status(&mut self) -> Result<Status, Error<I::Error>> {
+        add_await([self.inner.get_status()])
+    }
+
+    // The power state of the config is ignored, use power_on/power_off for that
+    pub async fn apply_config(&mut self, config: Config) -> Result<(), Error<I::Error>> {
+        let config = config.with_powered_on(S::POWERED_ON);
+        add_await([self.inner.apply_config(config)])
+    }
+
+    pub async fn set_again(&mut self, gain: Gain) -> Result<(), Error<I::Error>> {
+        add_await([self.inner.set_again(gain)])
+    }
+
+    pub async fn set_atime(&mut self, time: Integration) -> Result<(), Error<I::Error>> {
+        add_await([self.inner.set_atime(time)])
+    }
+
+    pub fn set_auto_range(&mut self, auto_range: Option<AutoRange>) {
+        self.inner.set_auto_range(auto_range);
+    }
+
+    pub async fn set_persist(&mut self, persist: Persist) -> Result<(), Error<I::Error>> {
+        add_await([self.inner.set_persist(persist)])
+    }
+
+    pub async fn set_threshold(&mut self, lower: u16, upper: u16) -> Result<(), Error<I::Error>> {
+        add_await([self.inner.set_threshold(lower, upper)])
+    }
+
+    pub async fn set_threshold_lux(
+        &mut self,
+        lower: Lux,
+        upper: Lux,
+    ) -> Result<(), Error<I::Error>> {
+        add_await([self.inner.set_threshold_lux(lower, upper)])
+    }
+
+    pub async fn set_np_threshold(
+        &mut self,
+        lower: u16,
+        upper: u16,
+    ) -> Result<(), Error<I::Error>> {
+        add_await([self.inner.set_np_threshold(lower, upper)])
+    }
+
+    pub async fn set_lux_model(&mut self, model: M) -> Result<(), Error<I::Error>> {
+        add_await([self.inner.set_lux_model(model)])
+    }
+
+    pub async fn set_calibration(
+        &mut self,
+        calibration: Calibration,
+    ) -> Result<(), Error<I::Error>> {
+        add_await([self.inner.set_calibration(calibration)])
+    }
+
+    pub fn set_tracking_window(&mut self, window: Option<TrackingWindow>) {
+        self.inner.set_tracking_window(window);
+    }
+
+    pub async fn enable_interrupt(&mut self, enable: bool) -> Result<(), Error<I::Error>> {
+        add_await([self.inner.enable_interrupt(enable)])
+    }
+
+    pub async fn enable_np_interrupt(&mut self, enable: bool) -> Result<(), Error<I::Error>> {
+        add_await([self.inner.enable_np_interrupt(enable)])
+    }
+
+    pub async fn enable_sleep_after_interrupt(
+        &mut self,
+        enable: bool,
+    ) -> Result<(), Error<I::Error>> {
+        add_await([self.inner.enable_sleep_after_interrupt(enable)])
+    }
+
+    pub async fn special_function(
+        &mut self,
+        function: SpecialFunction,
+    ) -> Result<(), Error<I::Error>> {
+        add_await([self.inner.special_function(function)])
+    }
+
+    pub async fn clear_interrupt(&mut self) -> Result<(), Error<I::Error>> {
+        add_await([self.inner.clear_interrupt()])
+    }
+
+    pub async fn clear_np_interrupt(&mut self) -> Result<(), Error<I::Error>> {
+        add_await([self.inner.clear_np_interrupt()])
+    }
+}
+
+#[duplicate_item(
+    _tsl2591_ async add_await(code) _generics_ _with_state_(state);
+    [Tsl2591] [] [code] [I: embedded_hal::i2c::I2c, M: LuxModel] [I, state, M];
+    [Tsl2591Async] [async] [code.await] [I: embedded_hal_async::i2c::I2c, P, M: LuxModel]
+        [I, state, P, M];
+)]
+impl<_generics_> _tsl2591_<_with_state_([PoweredOff])> {
+    pub async fn power_on(
+        mut self,
+    ) -> Transition<_tsl2591_<_with_state_([PoweredOn])>, Self, I::Error> {
+        match add_await([self.inner.power_on()]) {
+            Ok(()) => Ok(self.into_state()),
+            Err(error) => Err((self, error)),
+        }
+    }
+}
+
+#[duplicate_item(
+    _tsl2591_ _hal_ async add_await(code) _generics_ _with_state_(state);
+    [Tsl2591] [embedded_hal] [] [code] [I: embedded_hal::i2c::I2c, M: LuxModel] [I, state, M];
+    [Tsl2591Async] [embedded_hal_async] [async] [code.await]
+        [I: embedded_hal_async::i2c::I2c, P, M: LuxModel] [I, state, P, M];
+)]
+impl<_generics_> _tsl2591_<_with_state_([PoweredOn])> {
+    pub async fn power_off(
+        mut self,
+    ) -> Transition<_tsl2591_<_with_state_([PoweredOff])>, Self, I::Error> {
+        match add_await([self.inner.power_off()]) {
+            Ok(()) => Ok(self.into_state()),
+            Err(error) => Err((self, error)),
+        }
+    }
+
+    pub async fn is_cycle_complete(&mut self) -> Result<bool, Error<I::Error>> {
+        add_await([self.inner.is_cycle_complete()])
+    }
+
+    pub async fn get_raw_als_data(
+        &mut self,
+        check_complete: bool,
+    ) -> Result<AlsReading, Error<I::Error>> {
+        add_await([self.inner.get_raw_als_data(check_complete)])
+    }
+
+    pub async fn get_lux(&mut self, check_complete: bool) -> Result<Lux, Error<I::Error>> {
+        add_await([self.inner.get_lux(check_complete)])
+    }
+
+    pub async fn get_irradiance(
+        &mut self,
+        check_complete: bool,
+    ) -> Result<Irradiance, Error<I::Error>> {
+        add_await([self.inner.get_irradiance(check_complete)])
+    }
+
+    #[cfg(feature = "float")]
+    #[duplicate_item(
+        get_lux_float float;
+        [get_lux_f32] [f32];
+        [get_lux_f64] [f64];
+    )]
+    pub async fn get_lux_float(&mut self, check_complete: bool) -> Result<float, Error<I::Error>> {
+        add_await([self.inner.get_lux_float(check_complete)])
+    }
+
+    pub async fn get_lux_and_track(&mut self) -> Result<Lux, Error<I::Error>> {
+        add_await([self.inner.get_lux_and_track()])
+    }
+
+    pub async fn wait_for_cycle<D: _hal_::delay::DelayNs>(
+        &mut self,
+        delay: &mut D,
+    ) -> Result<(), Error<I::Error>> {
+        add_await([self.inner.wait_for_cycle(delay)])
+    }
+
+    pub async fn measure_lux<D: _hal_::delay::DelayNs>(
+        &mut self,
+        delay: &mut D,
+    ) -> Result<Lux, Error<I::Error>> {
+        add_await([self.inner.measure_lux(delay)])
+    }
+
+    pub async fn clear_interrupt_and_resume(&mut self) -> Result<(), Error<I::Error>> {
+        add_await([self.inner.clear_interrupt_and_resume()])
+    }
+}
+
+impl<I: embedded_hal_async::i2c::I2c, S, P, M> Tsl2591Async<I, S, P, M> {
+    pub fn with_int_pin<W: embedded_hal_async::digital::Wait>(
+        self,
+        int_pin: W,
+    ) -> Tsl2591Async<I, S, W, M> {
+        Tsl2591Async {
+            inner: self.inner.with_int_pin(int_pin),
+            state: PhantomData,
+        }
+    }
+}
+
+impl<I: embedded_hal_async::i2c::I2c, P: embedded_hal_async::digital::Wait, M: LuxModel>
+    Tsl2591Async<I, PoweredOn, P, M>
+{
+    pub async fn wait_for_threshold_event(&mut self) -> Result<Lux, Error<I::Error>> {
+        self.inner.wait_for_threshold_event().await
+    }
+
+    pub async fn wait_for_new_sample(&mut self) -> Result<Lux, Error<I::Error>> {
+        self.inner.wait_for_new_sample().await
+    }
+}
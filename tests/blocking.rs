@@ -0,0 +1,682 @@
+mod common;
+
+use common::{reg, Sim, SimError, SimI2c};
+use tsl2591_rs::register::{Enable, Register};
+use tsl2591_rs::*;
+
+fn setup() -> (Sim, Tsl2591<SimI2c>) {
+    let sim = Sim::new();
+    let tsl2591 = Tsl2591::new(sim.i2c()).expect("Failed to init sensor");
+    (sim, tsl2591)
+}
+
+fn lux_value(lux: Lux) -> f64 {
+    lux.integer as f64 + lux.fractional as f64 / 1_000_000f64
+}
+
+#[test]
+fn new_resets_and_powers_on() {
+    let (sim, tsl2591) = setup();
+
+    assert!(tsl2591.powered_on);
+    assert_eq!(sim.reg(reg::ENABLE), 0b0000_0011);
+    assert_eq!(sim.reg(reg::CONFIG), 0x00);
+}
+
+#[test]
+fn new_rejects_wrong_id() {
+    let sim = Sim::new();
+    sim.set_dev_id(0x42);
+
+    assert!(matches!(
+        Tsl2591::new(sim.i2c()),
+        Err(Error::InvalidId(0x42))
+    ));
+}
+
+#[test]
+fn i2c_errors_are_propagated() {
+    let (sim, mut tsl2591) = setup();
+    sim.device().fail_transactions = 1;
+
+    assert!(matches!(
+        tsl2591.get_id(),
+        Err(Error::I2cError(SimError::Injected))
+    ));
+    assert_eq!(tsl2591.get_id().unwrap(), common::DEV_ID);
+}
+
+#[test]
+fn get_lux_requires_complete_cycle() {
+    let (sim, mut tsl2591) = setup();
+    sim.set_light(10.0, 2.0);
+
+    assert!(matches!(tsl2591.get_lux(true), Err(Error::CycleIncomplete)));
+
+    sim.advance_ms(100);
+    let lux = tsl2591.get_lux(true).unwrap();
+    assert_eq!((lux.integer, lux.fractional), (2611, 200_000));
+
+    // Reading re-arms AVALID, so the next cycle has to complete first
+    assert!(matches!(tsl2591.get_lux(true), Err(Error::CycleIncomplete)));
+}
+
+#[test]
+fn raw_reading_separates_channels() {
+    let (sim, mut tsl2591) = setup();
+    sim.set_light(10.0, 2.0);
+    sim.advance_ms(100);
+
+    let reading = tsl2591.get_raw_als_data(true).unwrap();
+    assert_eq!(reading.full_spectrum(), 1000);
+    assert_eq!(reading.infrared(), 200);
+    assert_eq!(reading.visible(), 800);
+
+    // Noise can read more IR than full spectrum in the dark
+    assert_eq!(AlsReading::new(5, 7).visible(), 0);
+}
+
+#[test]
+fn infrared_above_full_spectrum_reads_zero_lux() {
+    let (sim, mut tsl2591) = setup();
+    sim.set_light(2.0, 3.0);
+    sim.advance_ms(100);
+
+    let lux = tsl2591.get_lux(true).unwrap();
+    assert_eq!(lux, Lux::ZERO);
+}
+
+#[test]
+fn get_raw_als_data_reports_saturation() {
+    let (sim, mut tsl2591) = setup();
+    sim.set_light(1000.0, 10.0);
+    sim.advance_ms(100);
+
+    match tsl2591.get_raw_als_data(true) {
+        Err(Error::AdcSaturated(data)) => assert_eq!(data.infrared(), 1000),
+        _ => panic!("Expected saturated reading"),
+    }
+}
+
+#[test]
+fn gain_and_integration_scale_lux() {
+    let (sim, mut tsl2591) = setup();
+    sim.set_light(10.0, 2.0);
+    sim.advance_ms(100);
+    let low = lux_value(tsl2591.get_lux(true).unwrap());
+
+    tsl2591.set_again(Gain::Med).unwrap();
+    tsl2591.set_atime(Integration::T200ms).unwrap();
+    sim.advance_ms(200);
+    let med = lux_value(tsl2591.get_lux(true).unwrap());
+
+    assert_eq!(sim.reg(reg::CONFIG), 0x11);
+    assert!((low - med).abs() < 0.01, "{low} != {med}");
+}
+
+#[test]
+fn get_config_and_sync_from_chip() {
+    let (sim, mut tsl2591) = setup();
+    tsl2591.set_again(Gain::High).unwrap();
+    tsl2591.set_atime(Integration::T300ms).unwrap();
+    tsl2591.set_persist(Persist::F10).unwrap();
+    tsl2591.set_threshold(100, 5000).unwrap();
+    tsl2591.set_np_threshold(10, 60000).unwrap();
+    tsl2591.enable_interrupt(true).unwrap();
+
+    let config = tsl2591.get_config().unwrap();
+    assert_eq!(config.gain, Gain::High);
+    assert_eq!(config.integration, Integration::T300ms);
+    assert_eq!(config.persist, Persist::F10);
+    assert_eq!(
+        (config.lower_threshold, config.upper_threshold),
+        (100, 5000)
+    );
+    assert_eq!(
+        (config.np_lower_threshold, config.np_upper_threshold),
+        (10, 60000)
+    );
+    assert!(config.powered_on && config.interrupt_enabled);
+    assert!(!config.np_interrupt_enabled && !config.sleep_after_interrupt);
+
+    // Simulate a brown-out putting the chip back to its defaults behind our back
+    sim.poke(reg::CONFIG, 0x00);
+    sim.poke(reg::ENABLE, 0x00);
+    tsl2591.sync_from_chip().unwrap();
+    assert!(!tsl2591.powered_on);
+
+    tsl2591.power_on().unwrap();
+    sim.set_light(10.0, 2.0);
+    sim.advance_ms(100);
+    let lux = tsl2591.get_lux(true).unwrap();
+    assert_eq!((lux.integer, lux.fractional), (2611, 200_000));
+}
+
+#[test]
+fn apply_config_writes_everything_at_once() {
+    let (sim, mut tsl2591) = setup();
+    let config = Config::new()
+        .with_gain(Gain::High)
+        .with_integration(Integration::T300ms)
+        .with_persist(Persist::F5)
+        .with_threshold(100, 20_000)
+        .with_np_threshold(10, 60_000)
+        .with_interrupt(true)
+        .with_np_interrupt(true);
+
+    let before = sim.transactions();
+    tsl2591.apply_config(config).unwrap();
+    assert_eq!(sim.transactions() - before, 3);
+
+    assert_eq!(tsl2591.get_config().unwrap(), config);
+    assert_eq!(sim.reg(reg::ENABLE), 0b1001_0011);
+
+    // Cached settings follow, so lux uses the new scale
+    sim.set_light(0.1, 0.02);
+    sim.advance_ms(300);
+    let lux = tsl2591.get_lux(true).unwrap();
+    assert_eq!(lux.integer, 26);
+
+    let config = config
+        .with_sleep_after_interrupt(true)
+        .with_powered_on(false);
+    tsl2591.apply_config(config).unwrap();
+    assert!(!tsl2591.powered_on);
+    assert_eq!(tsl2591.get_config().unwrap(), config);
+}
+
+#[test]
+fn with_config_constructs_configured_driver() {
+    let sim = Sim::new();
+    let config = Config::default()
+        .with_gain(Gain::Max)
+        .with_persist(Persist::F60);
+    let mut tsl2591 = Tsl2591::with_config(sim.i2c(), config).unwrap();
+
+    assert!(tsl2591.powered_on);
+    assert_eq!(tsl2591.get_config().unwrap(), config);
+}
+
+#[test]
+fn persist_filter_delays_interrupt() {
+    let (sim, mut tsl2591) = setup();
+    tsl2591.set_threshold(100, 500).unwrap();
+    tsl2591.set_persist(Persist::F2).unwrap();
+    tsl2591.enable_interrupt(true).unwrap();
+    sim.set_light(10.0, 2.0);
+
+    sim.advance_ms(100);
+    assert!(!tsl2591.get_status().unwrap().aint);
+    sim.advance_ms(100);
+    assert!(tsl2591.get_status().unwrap().aint);
+    assert!(sim.device().int_asserted());
+
+    tsl2591.clear_interrupt().unwrap();
+    assert!(!tsl2591.get_status().unwrap().aint);
+}
+
+#[test]
+fn lux_thresholds_follow_gain_and_integration() {
+    let (sim, mut tsl2591) = setup();
+    let threshold = |sim: &Sim| {
+        let lower = u16::from_le_bytes([sim.reg(reg::AILTL), sim.reg(reg::AILTL + 1)]);
+        let upper = u16::from_le_bytes([sim.reg(reg::AIHTL), sim.reg(reg::AIHTL + 1)]);
+        (lower, upper)
+    };
+
+    let lower = Lux {
+        integer: 50,
+        fractional: 0,
+    };
+    let upper = Lux {
+        integer: 1000,
+        fractional: 500_000,
+    };
+    tsl2591.set_threshold_lux(lower, upper).unwrap();
+    assert_eq!(threshold(&sim), (12, 245));
+
+    tsl2591.set_again(Gain::Med).unwrap();
+    assert_eq!(threshold(&sim), (306, 6130));
+
+    tsl2591.set_atime(Integration::T600ms).unwrap();
+    assert_eq!(threshold(&sim), (1838, 36783));
+
+    // Raw thresholds stop the lux thresholds from being recomputed
+    tsl2591.set_threshold(1, 2).unwrap();
+    tsl2591.set_again(Gain::Low).unwrap();
+    assert_eq!(threshold(&sim), (1, 2));
+}
+
+#[test]
+fn irradiance_scales_with_gain_and_integration() {
+    let (sim, mut tsl2591) = setup();
+    sim.set_light(10.0, 2.0);
+    sim.advance_ms(100);
+
+    let irradiance = tsl2591.get_irradiance(true).unwrap();
+    assert_eq!(
+        irradiance,
+        Irradiance {
+            full_spectrum: 1_514_577,
+            infrared: 519_143,
+        }
+    );
+
+    // Same light at 25x gain and 2x the integration time gives the same irradiance
+    tsl2591.set_again(Gain::Med).unwrap();
+    tsl2591.set_atime(Integration::T200ms).unwrap();
+    sim.advance_ms(200);
+    let irradiance = tsl2591.get_irradiance(true).unwrap();
+    assert_eq!(irradiance.full_spectrum, 1_514_577);
+    assert_eq!(irradiance.infrared, 519_143);
+}
+
+#[test]
+fn light_source_from_driver_reading() {
+    let (sim, mut tsl2591) = setup();
+    sim.set_light(10.0, 3.0);
+    sim.advance_ms(100);
+
+    let reading = tsl2591.get_raw_als_data(true).unwrap();
+    assert_eq!(
+        LightSourceClassifier::default().classify(reading).source,
+        LightSource::Daylight
+    );
+}
+
+#[test]
+fn lux_model_is_selectable() {
+    let (sim, mut tsl2591) = setup();
+    tsl2591.set_again(Gain::High).unwrap();
+    sim.set_light(0.1, 0.02);
+    sim.advance_ms(100);
+
+    let lux = tsl2591.get_lux(false).unwrap();
+    assert_eq!((lux.integer, lux.fractional), (26, 112_000));
+
+    // Adafruit's typical 428x gain figure reads a little lower than the nominal 400x
+    let mut tsl2591 = tsl2591.with_lux_model(AdafruitLux);
+    let lux = tsl2591.get_lux(false).unwrap();
+    assert_eq!((lux.integer, lux.fractional), (24, 403_738));
+
+    let mut tsl2591 = tsl2591.with_lux_model(Dn40Lux::TSL2591);
+    let lux = tsl2591.get_lux(false).unwrap();
+    assert_eq!((lux.integer, lux.fractional), (27, 417_600));
+
+    // Coefficients only known at runtime, e.g. read back from a calibration in flash
+    let coefficients = [408, 1000, 0, 0];
+    tsl2591
+        .set_lux_model(Dn40Lux {
+            device_factor: coefficients[0],
+            coef_b: coefficients[1],
+            coef_c: coefficients[2],
+            coef_d: coefficients[3],
+        })
+        .unwrap();
+    let lux = tsl2591.get_lux(false).unwrap();
+    assert_eq!((lux.integer, lux.fractional), (32, 640_000));
+}
+
+#[test]
+fn lux_thresholds_follow_lux_model() {
+    let (sim, tsl2591) = setup();
+    let mut tsl2591 = tsl2591.with_lux_model(Dn40Lux::TSL2591);
+    let lower = |sim: &Sim| u16::from_le_bytes([sim.reg(reg::AILTL), sim.reg(reg::AILTL + 1)]);
+    tsl2591.set_again(Gain::High).unwrap();
+
+    let lux = |integer| Lux {
+        integer,
+        fractional: 0,
+    };
+    tsl2591.set_threshold_lux(lux(10), lux(20)).unwrap();
+    assert_eq!(lower(&sim), 980);
+
+    // A device factor twice as large halves the counts needed for the same lux
+    tsl2591
+        .set_lux_model(Dn40Lux {
+            device_factor: 816,
+            ..Dn40Lux::TSL2591
+        })
+        .unwrap();
+    assert_eq!(lower(&sim), 490);
+}
+
+#[test]
+fn calibration_corrects_lux() {
+    let (sim, mut tsl2591) = setup();
+    let lux = |integer, fractional| Lux {
+        integer,
+        fractional,
+    };
+    sim.set_light(10.0, 2.0);
+    sim.advance_ms(100);
+
+    // Tinted glass passing 40% of the light
+    tsl2591.set_calibration(Calibration::glass(2500)).unwrap();
+    let reading = tsl2591.get_lux(false).unwrap();
+    assert_eq!((reading.integer, reading.fractional), (6528, 0));
+
+    let calibration =
+        Calibration::two_point((lux(100, 0), lux(120, 0)), (lux(1000, 0), lux(1110, 0)));
+    assert_eq!(
+        calibration,
+        Some(Calibration {
+            glass_attenuation: 1000,
+            slope: 1_100_000,
+            offset: 10_000_000,
+        })
+    );
+    tsl2591.set_calibration(calibration.unwrap()).unwrap();
+    let reading = tsl2591.get_lux(false).unwrap();
+    assert_eq!((reading.integer, reading.fractional), (2882, 320_000));
+
+    assert_eq!(
+        Calibration::single_point(lux(400, 0), lux(1000, 0)),
+        Some(Calibration::glass(2500))
+    );
+    assert_eq!(Calibration::single_point(lux(0, 0), lux(1000, 0)), None);
+    assert_eq!(Calibration::single_point(lux(400, 0), lux(0, 0)), None);
+    assert_eq!(Calibration::single_point(lux(400, 0), lux(0, 1)), None);
+    assert_eq!(
+        Calibration::two_point((lux(100, 0), lux(120, 0)), (lux(100, 0), lux(130, 0))),
+        None
+    );
+}
+
+#[test]
+fn lux_thresholds_follow_calibration() {
+    let (sim, mut tsl2591) = setup();
+    let threshold = |sim: &Sim| {
+        let lower = u16::from_le_bytes([sim.reg(reg::AILTL), sim.reg(reg::AILTL + 1)]);
+        let upper = u16::from_le_bytes([sim.reg(reg::AIHTL), sim.reg(reg::AIHTL + 1)]);
+        (lower, upper)
+    };
+
+    let lower = Lux {
+        integer: 50,
+        fractional: 0,
+    };
+    let upper = Lux {
+        integer: 1000,
+        fractional: 500_000,
+    };
+    tsl2591.set_threshold_lux(lower, upper).unwrap();
+    assert_eq!(threshold(&sim), (12, 245));
+
+    tsl2591.set_calibration(Calibration::glass(2500)).unwrap();
+    assert_eq!(threshold(&sim), (4, 98));
+}
+
+#[test]
+fn tracking_window_follows_light_level() {
+    let (sim, mut tsl2591) = setup();
+    tsl2591.set_persist(Persist::F1).unwrap();
+    tsl2591.enable_interrupt(true).unwrap();
+    tsl2591.set_tracking_window(Some(TrackingWindow::Relative(10)));
+    sim.set_light(10.0, 2.0);
+
+    // Default window of 0..0 fires straight away
+    sim.advance_ms(100);
+    assert!(sim.device().int_asserted());
+    tsl2591.get_lux_and_track().unwrap();
+    assert!(!sim.device().int_asserted());
+    assert_eq!(tsl2591.get_config().unwrap().lower_threshold, 900);
+
+    // Small changes stay inside the window
+    sim.set_light(10.5, 2.0);
+    sim.advance_ms(200);
+    assert!(!sim.device().int_asserted());
+
+    sim.set_light(12.0, 2.0);
+    sim.advance_ms(100);
+    assert!(sim.device().int_asserted());
+    tsl2591.get_lux_and_track().unwrap();
+
+    let config = tsl2591.get_config().unwrap();
+    assert_eq!(
+        (config.lower_threshold, config.upper_threshold),
+        (1080, 1320)
+    );
+}
+
+#[test]
+fn tracking_window_follows_auto_range() {
+    let (sim, mut tsl2591) = setup();
+    tsl2591.set_persist(Persist::F1).unwrap();
+    tsl2591.enable_interrupt(true).unwrap();
+    tsl2591.set_auto_range(Some(AutoRange::default()));
+    tsl2591.set_tracking_window(Some(TrackingWindow::Relative(10)));
+    sim.set_light(1.0, 0.2);
+
+    // 100 counts at Low/T100ms is dim enough to step up to Low/T400ms
+    sim.advance_ms(100);
+    assert!(sim.device().int_asserted());
+    tsl2591.get_lux_and_track().unwrap();
+
+    // The window is centred on the 400 counts expected at the new setting
+    let config = tsl2591.get_config().unwrap();
+    assert_eq!(config.integration, Integration::T400ms);
+    assert_eq!((config.lower_threshold, config.upper_threshold), (360, 440));
+
+    sim.advance_ms(800);
+    assert!(!sim.device().int_asserted());
+}
+
+#[test]
+fn no_persist_interrupt_ignores_persist_filter() {
+    let (sim, mut tsl2591) = setup();
+    tsl2591.set_threshold(100, 500).unwrap();
+    tsl2591.set_persist(Persist::F60).unwrap();
+    tsl2591.set_np_threshold(100, 500).unwrap();
+    tsl2591.enable_np_interrupt(true).unwrap();
+    sim.set_light(10.0, 2.0);
+
+    sim.advance_ms(100);
+    let status = tsl2591.get_status().unwrap();
+    assert!(status.npintr && !status.aint);
+    assert!(sim.device().int_asserted());
+
+    tsl2591.clear_np_interrupt().unwrap();
+    assert!(!tsl2591.get_status().unwrap().npintr);
+    assert!(!sim.device().int_asserted());
+}
+
+#[test]
+fn special_functions_force_and_clear_interrupts() {
+    let (_sim, mut tsl2591) = setup();
+    tsl2591.enable_interrupt(true).unwrap();
+
+    tsl2591
+        .special_function(SpecialFunction::ForceInterrupt)
+        .unwrap();
+    assert!(tsl2591.get_status().unwrap().aint);
+
+    tsl2591
+        .special_function(SpecialFunction::ClearAlsInterrupt)
+        .unwrap();
+    assert!(!tsl2591.get_status().unwrap().aint);
+}
+
+#[test]
+fn sleep_after_interrupt_stops_integration_until_cleared() {
+    let (sim, mut tsl2591) = setup();
+    tsl2591.enable_sleep_after_interrupt(true).unwrap();
+    tsl2591.enable_interrupt(true).unwrap();
+    sim.set_light(10.0, 2.0);
+
+    sim.advance_ms(100);
+    assert!(tsl2591.get_status().unwrap().aint);
+    tsl2591.get_lux(true).unwrap();
+
+    // Asleep, so no further cycles complete
+    sim.advance_ms(500);
+    assert!(!tsl2591.is_cycle_complete().unwrap());
+
+    tsl2591.clear_interrupt_and_resume().unwrap();
+    sim.advance_ms(100);
+    assert!(tsl2591.is_cycle_complete().unwrap());
+}
+
+#[test]
+fn measure_lux_waits_for_fresh_cycle() {
+    let (sim, mut tsl2591) = setup();
+    let mut delay = sim.delay();
+    tsl2591.set_atime(Integration::T600ms).unwrap();
+    sim.set_light(10.0, 2.0);
+
+    let lux = tsl2591.measure_lux(&mut delay).unwrap();
+    assert_eq!(lux.integer, 2611);
+}
+
+#[test]
+fn measure_lux_times_out_when_powered_off() {
+    let (sim, mut tsl2591) = setup();
+    let mut delay = sim.delay();
+    tsl2591.power_off().unwrap();
+
+    // Re-enabling AEN alone doesn't start the ADC without PON
+    let mut enable = Enable::from_bits(0);
+    enable.set_aen(true);
+    tsl2591.write_register(enable).unwrap();
+
+    assert!(matches!(
+        tsl2591.measure_lux(&mut delay),
+        Err(Error::CycleIncomplete)
+    ));
+}
+
+#[test]
+fn auto_range_recovers_from_saturation() {
+    let (sim, mut tsl2591) = setup();
+    let mut delay = sim.delay();
+    tsl2591.set_again(Gain::Max).unwrap();
+    tsl2591.set_auto_range(Some(AutoRange::default()));
+    sim.set_light(10.0, 2.0);
+
+    let lux = tsl2591.measure_lux(&mut delay).unwrap();
+    assert!((lux_value(lux) - 2611.2).abs() < 1.0);
+
+    // Walked down to the first rung that keeps CH0 below 80% of full scale
+    let config = tsl2591.get_config().unwrap();
+    assert_eq!(config.gain, Gain::Med);
+    assert_eq!(config.integration, Integration::T100ms);
+}
+
+#[test]
+fn auto_range_increases_sensitivity_in_the_dark() {
+    let (sim, mut tsl2591) = setup();
+    let mut delay = sim.delay();
+    tsl2591.set_auto_range(Some(AutoRange::default()));
+    sim.set_light(0.01, 0.002);
+
+    tsl2591.measure_lux(&mut delay).unwrap();
+    let config = tsl2591.get_config().unwrap();
+    assert_eq!(config.gain, Gain::Max);
+    assert_eq!(config.integration, Integration::T100ms);
+}
+
+#[test]
+fn auto_range_does_not_step_up_into_saturation() {
+    let (sim, mut tsl2591) = setup();
+    let mut delay = sim.delay();
+    tsl2591.set_again(Gain::High).unwrap();
+    tsl2591.set_atime(Integration::T400ms).unwrap();
+    tsl2591.set_auto_range(Some(AutoRange::default()));
+
+    // ~6500 counts is under 10% of full scale, but 5.75x that saturates Max/T100ms
+    sim.set_light(0.040625, 0.008);
+
+    for _ in 0..4 {
+        let lux = tsl2591.measure_lux(&mut delay).unwrap();
+        assert_eq!(lux.integer, 10);
+
+        let config = tsl2591.get_config().unwrap();
+        assert_eq!(config.gain, Gain::High);
+        assert_eq!(config.integration, Integration::T400ms);
+    }
+}
+
+#[test]
+fn write_register_masks_reserved_bits() {
+    let (sim, mut tsl2591) = setup();
+
+    tsl2591
+        .write_register(register::ControlReg::from_bits(0x7D))
+        .unwrap();
+    assert_eq!(sim.reg(reg::CONFIG), 0x35);
+
+    let config = tsl2591.get_config().unwrap();
+    assert_eq!(config.gain, Gain::Max);
+    assert_eq!(config.integration, Integration::T600ms);
+}
+
+#[test]
+fn settings_changes_keep_chip_powered_off() {
+    let (sim, mut tsl2591) = setup();
+    tsl2591.power_off().unwrap();
+
+    tsl2591.set_again(Gain::Med).unwrap();
+    tsl2591.set_persist(Persist::F10).unwrap();
+    tsl2591.set_threshold(1, 2).unwrap();
+    assert!(!tsl2591.powered_on);
+    assert_eq!(sim.reg(reg::ENABLE), 0);
+}
+
+#[test]
+fn typestate_driver_tracks_power_state() {
+    let sim = Sim::new();
+    let mut tsl2591 = typestate::Tsl2591::new(sim.i2c()).unwrap();
+    sim.set_light(10.0, 2.0);
+    sim.advance_ms(100);
+    assert_eq!(tsl2591.get_lux(true).unwrap().integer, 2611);
+
+    // Only configuration is available while powered off, and it leaves the chip off
+    let mut tsl2591 = tsl2591.power_off().map_err(|(_, error)| error).unwrap();
+    tsl2591.set_again(Gain::Med).unwrap();
+    tsl2591
+        .apply_config(Config::new().with_persist(Persist::F5))
+        .unwrap();
+    assert_eq!(sim.reg(reg::ENABLE), 0);
+
+    // A failed transition hands the handle back
+    sim.device().fail_transactions = 1;
+    let (tsl2591, error) = match tsl2591.power_on() {
+        Err(failed) => failed,
+        Ok(_) => panic!("Expected power_on to fail"),
+    };
+    assert!(matches!(error, Error::I2cError(SimError::Injected)));
+
+    let mut tsl2591 = tsl2591.power_on().map_err(|(_, error)| error).unwrap();
+    assert_eq!(sim.reg(reg::ENABLE), 0b0000_0011);
+    sim.advance_ms(100);
+    assert_eq!(tsl2591.get_lux(true).unwrap().integer, 2611);
+
+    let tsl2591 = tsl2591.release();
+    assert!(tsl2591.powered_on);
+}
+
+#[test]
+fn destroy_and_attach_keep_chip_state() {
+    let (sim, mut tsl2591) = setup();
+    tsl2591.set_again(Gain::High).unwrap();
+    tsl2591.set_atime(Integration::T200ms).unwrap();
+    tsl2591.set_persist(Persist::F3).unwrap();
+
+    let i2c = tsl2591.destroy();
+    let mut tsl2591 = Tsl2591::attach(i2c).unwrap();
+    assert!(tsl2591.powered_on);
+
+    // No reset, and the cached settings are picked up from the chip
+    let config = tsl2591.get_config().unwrap();
+    assert_eq!(config.gain, Gain::High);
+    assert_eq!(config.persist, Persist::F3);
+    sim.set_light(0.1, 0.02);
+    sim.advance_ms(200);
+    assert_eq!(tsl2591.get_lux(true).unwrap().integer, 26);
+
+    sim.set_dev_id(0x42);
+    assert!(matches!(
+        Tsl2591::attach(tsl2591.destroy()),
+        Err(Error::InvalidId(0x42))
+    ));
+}
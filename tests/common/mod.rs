@@ -0,0 +1,637 @@
+/* Register-level simulation of a TSL2591 for exercising the drivers on the host.
+ * Time is virtual and only moves forward through SimDelay, SimIntPin or Sim::advance_ms,
+ * so integration cycles, AVALID and interrupts are completely deterministic.
+ */
+#![allow(dead_code)]
+
+use core::future::{poll_fn, Future};
+use core::pin::pin;
+use core::task::{Context, Poll, Waker};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::Wake;
+
+use embedded_hal::i2c::{ErrorKind, NoAcknowledgeSource, Operation};
+
+pub const I2C_ADDR: u8 = 0x29;
+pub const DEV_ID: u8 = 0x50;
+
+pub mod reg {
+    pub const ENABLE: u8 = 0x00;
+    pub const CONFIG: u8 = 0x01;
+    pub const AILTL: u8 = 0x04;
+    pub const AIHTL: u8 = 0x06;
+    pub const NPAILTL: u8 = 0x08;
+    pub const NPAIHTL: u8 = 0x0A;
+    pub const PERSIST: u8 = 0x0C;
+    pub const PID: u8 = 0x11;
+    pub const ID: u8 = 0x12;
+    pub const STATUS: u8 = 0x13;
+    pub const C0DATAL: u8 = 0x14;
+    pub const C1DATAL: u8 = 0x16;
+}
+
+const NS_PER_MS: u64 = 1_000_000;
+
+// Number of consecutive out-of-range cycles required by each APERS value
+const PERSIST_CYCLES: [u32; 16] = [0, 1, 2, 3, 5, 10, 15, 20, 25, 30, 35, 40, 45, 50, 55, 60];
+
+// ENABLE bits
+const PON: u8 = 1 << 0;
+const AEN: u8 = 1 << 1;
+const AIEN: u8 = 1 << 4;
+const SAI: u8 = 1 << 6;
+const NPIEN: u8 = 1 << 7;
+
+// STATUS bits
+const AVALID: u8 = 1 << 0;
+const AINT: u8 = 1 << 4;
+const NPINTR: u8 = 1 << 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimError {
+    Nack,
+    BadCommand(u8),
+    Injected,
+}
+
+impl embedded_hal::i2c::Error for SimError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            SimError::Nack => ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address),
+            _ => ErrorKind::Other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PinError;
+
+impl embedded_hal::digital::Error for PinError {
+    fn kind(&self) -> embedded_hal::digital::ErrorKind {
+        embedded_hal::digital::ErrorKind::Other
+    }
+}
+
+pub struct Device {
+    regs: [u8; 0x20],
+    pointer: u8,
+    now_ns: u64,
+    cycle_start_ns: u64,
+    out_of_range_cycles: u32,
+
+    // Value the ID register takes after a reset, to impersonate some other chip
+    pub dev_id: u8,
+
+    // Light hitting each photodiode, in ADC counts per millisecond at 1x gain
+    ch0_rate: f64,
+    ch1_rate: f64,
+
+    // Every bus transaction addressed to the sensor, for checking bus traffic
+    pub transactions: usize,
+
+    // Fail this many upcoming transactions, to exercise error handling
+    pub fail_transactions: usize,
+}
+
+impl Device {
+    fn new() -> Self {
+        let mut device = Device {
+            regs: [0; 0x20],
+            pointer: 0,
+            now_ns: 0,
+            cycle_start_ns: 0,
+            out_of_range_cycles: 0,
+            dev_id: DEV_ID,
+            ch0_rate: 0.0,
+            ch1_rate: 0.0,
+            transactions: 0,
+            fail_transactions: 0,
+        };
+        device.software_reset();
+        device
+    }
+
+    fn software_reset(&mut self) {
+        self.regs = [0; 0x20];
+        self.regs[reg::ID as usize] = self.dev_id;
+        self.out_of_range_cycles = 0;
+    }
+
+    pub fn reg(&self, addr: u8) -> u8 {
+        self.regs[addr as usize]
+    }
+
+    // Backdoor write that bypasses the bus, like another master or a brown-out would
+    pub fn poke(&mut self, addr: u8, val: u8) {
+        self.regs[addr as usize] = val;
+    }
+
+    fn reg16(&self, addr: u8) -> u16 {
+        u16::from_le_bytes([self.reg(addr), self.reg(addr + 1)])
+    }
+
+    fn gain(&self) -> f64 {
+        match (self.reg(reg::CONFIG) >> 4) & 0x3 {
+            0 => 1.0,
+            1 => 25.0,
+            2 => 400.0,
+            _ => 9200.0,
+        }
+    }
+
+    fn atime_ms(&self) -> u64 {
+        ((self.reg(reg::CONFIG) & 0x7) as u64 + 1) * 100
+    }
+
+    fn max_count(&self) -> u16 {
+        // The ADC can only count up to 1024 per 2.73ms step, which limits the 100ms setting
+        if self.atime_ms() == 100 {
+            37888
+        } else {
+            65535
+        }
+    }
+
+    pub fn int_asserted(&self) -> bool {
+        let enable = self.reg(reg::ENABLE);
+        let status = self.reg(reg::STATUS);
+        (enable & AIEN != 0 && status & AINT != 0) || (enable & NPIEN != 0 && status & NPINTR != 0)
+    }
+
+    fn adc_running(&self) -> bool {
+        let enable = self.reg(reg::ENABLE);
+        let asleep = enable & SAI != 0 && self.int_asserted();
+        enable & (PON | AEN) == PON | AEN && !asleep
+    }
+
+    pub fn set_light(&mut self, ch0_rate: f64, ch1_rate: f64) {
+        self.ch0_rate = ch0_rate;
+        self.ch1_rate = ch1_rate;
+    }
+
+    pub fn advance_ms(&mut self, ms: u64) {
+        self.advance_ns(ms * NS_PER_MS);
+    }
+
+    fn advance_ns(&mut self, ns: u64) {
+        self.now_ns += ns;
+
+        let cycle_ns = self.atime_ms() * NS_PER_MS;
+        while self.adc_running() && self.now_ns >= self.cycle_start_ns + cycle_ns {
+            self.cycle_start_ns += cycle_ns;
+            self.complete_cycle();
+        }
+    }
+
+    fn counts(&self, rate: f64) -> u16 {
+        let counts = rate * self.atime_ms() as f64 * self.gain();
+        counts.min(self.max_count() as f64) as u16
+    }
+
+    fn complete_cycle(&mut self) {
+        let ch0 = self.counts(self.ch0_rate);
+        let ch1 = self.counts(self.ch1_rate);
+        self.regs[reg::C0DATAL as usize..reg::C0DATAL as usize + 2]
+            .copy_from_slice(&ch0.to_le_bytes());
+        self.regs[reg::C1DATAL as usize..reg::C1DATAL as usize + 2]
+            .copy_from_slice(&ch1.to_le_bytes());
+
+        let mut status = self.reg(reg::STATUS) | AVALID;
+
+        // Persist-filtered interrupt, APERS of 0 interrupts on every cycle
+        let apers = (self.reg(reg::PERSIST) & 0xF) as usize;
+        if ch0 < self.reg16(reg::AILTL) || ch0 > self.reg16(reg::AIHTL) {
+            self.out_of_range_cycles += 1;
+        } else {
+            self.out_of_range_cycles = 0;
+        }
+        if apers == 0 || self.out_of_range_cycles >= PERSIST_CYCLES[apers] {
+            status |= AINT;
+        }
+
+        // No-persist interrupt
+        if ch0 < self.reg16(reg::NPAILTL) || ch0 > self.reg16(reg::NPAIHTL) {
+            status |= NPINTR;
+        }
+
+        self.regs[reg::STATUS as usize] = status;
+    }
+
+    fn restart_cycle(&mut self) {
+        self.cycle_start_ns = self.now_ns;
+        self.regs[reg::STATUS as usize] &= !AVALID;
+    }
+
+    fn write_reg(&mut self, addr: u8, val: u8) {
+        let was_running = self.adc_running();
+
+        match addr {
+            reg::ENABLE => {
+                self.regs[addr as usize] = val & 0b1101_0011;
+                if val & AEN == 0 {
+                    self.regs[reg::STATUS as usize] &= !AVALID;
+                }
+            }
+            reg::CONFIG => {
+                if val & 0x80 != 0 {
+                    self.software_reset();
+                } else {
+                    self.regs[addr as usize] = val & 0b0011_0111;
+                }
+            }
+            0x04..=0x0B => self.regs[addr as usize] = val,
+            reg::PERSIST => self.regs[addr as usize] = val & 0x0F,
+            // Everything else is read-only
+            _ => {}
+        }
+
+        if !was_running && self.adc_running() {
+            self.restart_cycle();
+        }
+    }
+
+    fn special_function(&mut self, function: u8) -> Result<(), SimError> {
+        let was_running = self.adc_running();
+        let status = &mut self.regs[reg::STATUS as usize];
+
+        match function {
+            0x04 => *status |= AINT,
+            0x06 => *status &= !AINT,
+            0x07 => *status &= !(AINT | NPINTR),
+            0x0A => *status &= !NPINTR,
+            _ => return Err(SimError::BadCommand(function)),
+        }
+
+        // Clearing the interrupt wakes the chip back up when sleep-after-interrupt is set
+        if !was_running && self.adc_running() {
+            self.restart_cycle();
+        }
+
+        Ok(())
+    }
+
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), SimError> {
+        if address != I2C_ADDR {
+            return Err(SimError::Nack);
+        }
+
+        self.transactions += 1;
+        if self.fail_transactions > 0 {
+            self.fail_transactions -= 1;
+            return Err(SimError::Injected);
+        }
+
+        for operation in operations {
+            match operation {
+                Operation::Write(bytes) => {
+                    let Some((&command, data)) = bytes.split_first() else {
+                        continue;
+                    };
+
+                    // Command: CMD:7 | TRANSACTION:6:5 | ADDR/SF:4:0
+                    if command & 0x80 == 0 {
+                        return Err(SimError::BadCommand(command));
+                    }
+                    match (command >> 5) & 0x3 {
+                        0b01 => {
+                            self.pointer = command & 0x1F;
+                            for &byte in data.iter() {
+                                self.write_reg(self.pointer, byte);
+                                self.pointer = (self.pointer + 1) & 0x1F;
+                            }
+                        }
+                        0b11 => self.special_function(command & 0x1F)?,
+                        _ => return Err(SimError::BadCommand(command)),
+                    }
+                }
+                Operation::Read(buf) => {
+                    for byte in buf.iter_mut() {
+                        *byte = self.reg(self.pointer);
+                        self.pointer = (self.pointer + 1) & 0x1F;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct Sim(Rc<RefCell<Device>>);
+
+impl Sim {
+    pub fn new() -> Self {
+        Sim(Rc::new(RefCell::new(Device::new())))
+    }
+
+    pub fn device(&self) -> std::cell::RefMut<'_, Device> {
+        self.0.borrow_mut()
+    }
+
+    pub fn i2c(&self) -> SimI2c {
+        SimI2c(self.clone())
+    }
+
+    pub fn delay(&self) -> SimDelay {
+        SimDelay(self.clone())
+    }
+
+    pub fn int_pin(&self) -> SimIntPin {
+        SimIntPin(self.clone())
+    }
+
+    pub fn set_light(&self, ch0_rate: f64, ch1_rate: f64) {
+        self.device().set_light(ch0_rate, ch1_rate);
+    }
+
+    pub fn advance_ms(&self, ms: u64) {
+        self.device().advance_ms(ms);
+    }
+
+    pub fn reg(&self, addr: u8) -> u8 {
+        self.device().reg(addr)
+    }
+
+    pub fn poke(&self, addr: u8, val: u8) {
+        self.device().poke(addr, val);
+    }
+
+    pub fn set_dev_id(&self, id: u8) {
+        let mut device = self.device();
+        device.dev_id = id;
+        device.poke(reg::ID, id);
+    }
+
+    pub fn transactions(&self) -> usize {
+        self.device().transactions
+    }
+}
+
+pub struct SimI2c(Sim);
+
+impl embedded_hal::i2c::ErrorType for SimI2c {
+    type Error = SimError;
+}
+
+impl embedded_hal::i2c::I2c for SimI2c {
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        self.0.device().transaction(address, operations)
+    }
+}
+
+impl embedded_hal_async::i2c::I2c for SimI2c {
+    async fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        self.0.device().transaction(address, operations)
+    }
+}
+
+pub const MUX_ADDR: u8 = 0x70;
+
+// TCA9548A with a simulated TSL2591 on some of its channels
+#[derive(Clone)]
+pub struct SimMux(Rc<RefCell<MuxState>>);
+
+pub struct MuxState {
+    sensors: [Option<Sim>; 8],
+    control: u8,
+    selects: usize,
+}
+
+impl MuxState {
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), SimError> {
+        if address == MUX_ADDR {
+            for operation in operations {
+                match operation {
+                    Operation::Write(bytes) => {
+                        if let Some(&control) = bytes.last() {
+                            self.control = control;
+                            self.selects += 1;
+                        }
+                    }
+                    Operation::Read(buf) => buf.fill(self.control),
+                }
+            }
+            return Ok(());
+        }
+
+        // Sensors on several enabled channels would all answer at once
+        if self.control.count_ones() != 1 {
+            return Err(SimError::Nack);
+        }
+        match &self.sensors[self.control.trailing_zeros() as usize] {
+            Some(sim) => sim.device().transaction(address, operations),
+            None => Err(SimError::Nack),
+        }
+    }
+}
+
+impl SimMux {
+    pub fn new(channels: &[u8]) -> Self {
+        let mut sensors: [Option<Sim>; 8] = Default::default();
+        for &channel in channels {
+            sensors[channel as usize] = Some(Sim::new());
+        }
+
+        SimMux(Rc::new(RefCell::new(MuxState {
+            sensors,
+            control: 0,
+            selects: 0,
+        })))
+    }
+
+    pub fn sensor(&self, channel: u8) -> Sim {
+        self.0.borrow().sensors[channel as usize]
+            .clone()
+            .expect("No sensor on channel")
+    }
+
+    pub fn i2c(&self) -> SimMuxI2c {
+        SimMuxI2c(self.clone())
+    }
+
+    pub fn selects(&self) -> usize {
+        self.0.borrow().selects
+    }
+}
+
+pub struct SimMuxI2c(SimMux);
+
+impl embedded_hal::i2c::ErrorType for SimMuxI2c {
+    type Error = SimError;
+}
+
+impl embedded_hal::i2c::I2c for SimMuxI2c {
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        self.0 .0.borrow_mut().transaction(address, operations)
+    }
+}
+
+impl embedded_hal_async::i2c::I2c for SimMuxI2c {
+    // Yields once like a real async bus would, so concurrent transactions can interleave
+    async fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        yield_now().await;
+        self.0 .0.borrow_mut().transaction(address, operations)
+    }
+}
+
+pub struct SimDelay(Sim);
+
+impl embedded_hal::delay::DelayNs for SimDelay {
+    fn delay_ns(&mut self, ns: u32) {
+        self.0.device().advance_ns(ns as u64);
+    }
+}
+
+impl embedded_hal_async::delay::DelayNs for SimDelay {
+    async fn delay_ns(&mut self, ns: u32) {
+        self.0.device().advance_ns(ns as u64);
+    }
+}
+
+// INT pin of the simulated chip, waiting on it lets virtual time run until the level changes
+pub struct SimIntPin(Sim);
+
+impl SimIntPin {
+    // Give up after a minute of virtual time rather than spinning forever
+    const TIMEOUT_MS: u64 = 60_000;
+
+    fn wait_for(&mut self, low: bool) -> Result<(), PinError> {
+        for _ in 0..Self::TIMEOUT_MS {
+            if self.0.device().int_asserted() == low {
+                return Ok(());
+            }
+            self.0.advance_ms(1);
+        }
+
+        Err(PinError)
+    }
+}
+
+impl embedded_hal::digital::ErrorType for SimIntPin {
+    type Error = PinError;
+}
+
+impl embedded_hal_async::digital::Wait for SimIntPin {
+    async fn wait_for_high(&mut self) -> Result<(), Self::Error> {
+        self.wait_for(false)
+    }
+
+    async fn wait_for_low(&mut self) -> Result<(), Self::Error> {
+        self.wait_for(true)
+    }
+
+    async fn wait_for_rising_edge(&mut self) -> Result<(), Self::Error> {
+        self.wait_for(true)?;
+        self.wait_for(false)
+    }
+
+    async fn wait_for_falling_edge(&mut self) -> Result<(), Self::Error> {
+        self.wait_for(false)?;
+        self.wait_for(true)
+    }
+
+    async fn wait_for_any_edge(&mut self) -> Result<(), Self::Error> {
+        let low = self.0.device().int_asserted();
+        self.wait_for(!low)
+    }
+}
+
+// Everything in the simulator completes immediately, so a busy-polling executor is enough
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let mut context = Context::from_waker(Waker::noop());
+
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+            return output;
+        }
+    }
+}
+
+// Polls both futures in turn until both are done, like two tasks on one executor
+pub fn join<A: Future, B: Future>(a: A, b: B) -> (A::Output, B::Output) {
+    let (mut a, mut b) = (pin!(a), pin!(b));
+    let (mut a_output, mut b_output) = (None, None);
+
+    block_on(poll_fn(|context| {
+        if a_output.is_none() {
+            if let Poll::Ready(output) = a.as_mut().poll(context) {
+                a_output = Some(output);
+            }
+        }
+        if b_output.is_none() {
+            if let Poll::Ready(output) = b.as_mut().poll(context) {
+                b_output = Some(output);
+            }
+        }
+
+        if a_output.is_some() && b_output.is_some() {
+            Poll::Ready((a_output.take().unwrap(), b_output.take().unwrap()))
+        } else {
+            Poll::Pending
+        }
+    }))
+}
+
+pub async fn yield_now() {
+    let mut yielded = false;
+    poll_fn(|context| {
+        if yielded {
+            return Poll::Ready(());
+        }
+
+        yielded = true;
+        context.waker().wake_by_ref();
+        Poll::Pending
+    })
+    .await
+}
+
+// Records whether it was woken, for checking a pending future isn't polled again until it should be
+#[derive(Default)]
+pub struct WakeFlag(AtomicBool);
+
+impl WakeFlag {
+    pub fn waker() -> (Arc<WakeFlag>, Waker) {
+        let flag = Arc::new(WakeFlag::default());
+        (flag.clone(), Waker::from(flag))
+    }
+
+    pub fn take(&self) -> bool {
+        self.0.swap(false, Ordering::SeqCst)
+    }
+}
+
+impl Wake for WakeFlag {
+    fn wake(self: Arc<Self>) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
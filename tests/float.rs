@@ -0,0 +1,62 @@
+#![cfg(feature = "float")]
+
+mod common;
+
+use common::{block_on, Sim};
+use tsl2591_rs::*;
+
+fn assert_close(actual: f64, expected: f64) {
+    assert!(
+        (actual - expected).abs() < 1e-3,
+        "{} is not close to {}",
+        actual,
+        expected
+    );
+}
+
+#[test]
+fn float_readings_match_fixed_point() {
+    let sim = Sim::new();
+    let mut tsl2591 = Tsl2591::new(sim.i2c()).unwrap();
+    sim.set_light(10.0, 2.0);
+    sim.advance_ms(100);
+
+    let lux = tsl2591.get_lux(false).unwrap();
+    assert_close(f64::from(lux), 2611.2);
+    assert_close(f32::from(lux) as f64, 2611.2);
+    assert_close(tsl2591.get_lux_f64(false).unwrap(), 2611.2);
+    assert_close(tsl2591.get_lux_f32(false).unwrap() as f64, 2611.2);
+
+    let mut tsl2591 = tsl2591.with_lux_model(Dn40Lux::TSL2591);
+    assert_close(tsl2591.get_lux_f64(false).unwrap(), 2741.76);
+
+    tsl2591.set_calibration(Calibration::glass(2500)).unwrap();
+    assert_close(tsl2591.get_lux_f32(false).unwrap() as f64, 6854.4);
+}
+
+#[test]
+fn async_driver_has_float_readings() {
+    let sim = Sim::new();
+    let tsl2591 = block_on(Tsl2591Async::new(sim.i2c())).unwrap();
+    let mut tsl2591 = tsl2591.with_lux_model(AdafruitLux);
+    sim.set_light(10.0, 2.0);
+    sim.advance_ms(100);
+
+    assert_close(block_on(tsl2591.get_lux_f64(true)).unwrap(), 2611.2);
+}
+
+#[test]
+fn float_calibration_helpers() {
+    let calibration = Calibration::two_point_f64((100.0, 120.0), (1000.0, 1110.0)).unwrap();
+    assert_close(calibration.apply_f64(2611.2), 2882.32);
+    assert_close(calibration.apply_f32(2611.2) as f64, 2882.32);
+
+    assert_eq!(
+        Calibration::single_point_f32(400.0, 1000.0),
+        Some(Calibration::glass(2500))
+    );
+    assert_eq!(Lux::from_f64(-1.5), Lux::from_micro_lux(-1_500_000));
+    assert_eq!(Lux::from_f32(0.25), Lux::from_milli_lux(250));
+    assert_eq!(Lux::from_f64(1e12), Lux::MAX);
+    assert_eq!(Lux::from_f32(-1e30), Lux::MIN);
+}
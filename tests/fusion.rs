@@ -0,0 +1,93 @@
+mod common;
+
+use common::{block_on, Sim, SimError, SimMux};
+use tsl2591_rs::mux::{Tca9548a, Tsl2591Array};
+use tsl2591_rs::*;
+
+fn lux(integer: i32) -> Result<Lux, Error<SimError>> {
+    Ok(Lux {
+        integer,
+        fractional: 0,
+    })
+}
+
+fn combined(fusion: Fusion<4>) -> Option<i32> {
+    let readings = [lux(100), lux(400), lux(200), lux(900)];
+    fusion.combine(readings).lux.map(|lux| lux.integer)
+}
+
+#[test]
+fn strategies_combine_valid_readings() {
+    assert_eq!(combined(Fusion::new(FusionStrategy::Mean)), Some(400));
+    assert_eq!(combined(Fusion::new(FusionStrategy::Median)), Some(300));
+    assert_eq!(combined(Fusion::new(FusionStrategy::Min)), Some(100));
+    assert_eq!(combined(Fusion::new(FusionStrategy::Max)), Some(900));
+    assert_eq!(combined(Fusion::weighted([3, 1, 0, 0])), Some(175));
+    assert_eq!(combined(Fusion::weighted([0; 4])), None);
+}
+
+#[test]
+fn unhealthy_sensors_are_left_out() {
+    let readings = [
+        lux(100),
+        Err(Error::AdcSaturated(AlsReading::new(65535, 100))),
+        lux(300),
+        Err(Error::I2cError(SimError::Nack)),
+        Err(Error::CycleIncomplete),
+    ];
+    let fused = Fusion::new(FusionStrategy::Median).combine(readings);
+
+    assert_eq!(fused.lux, Some(Lux::from_milli_lux(200_000)));
+    assert_eq!(fused.used, 2);
+    assert_eq!(fused.health[0].lux(), Some(Lux::from_milli_lux(100_000)));
+    assert_eq!(fused.health[1], SensorHealth::Saturated);
+    assert!(matches!(
+        fused.health[3],
+        SensorHealth::Failed(Error::I2cError(SimError::Nack))
+    ));
+    assert_eq!(fused.health[4], SensorHealth::NotReady);
+
+    let fused =
+        Fusion::new(FusionStrategy::Mean).combine([Err(Error::<SimError>::CycleIncomplete)]);
+    assert_eq!((fused.lux, fused.used), (None, 0));
+}
+
+#[test]
+fn sample_reads_separate_sensors() {
+    let sims = [Sim::new(), Sim::new(), Sim::new()];
+    let mut sensors = [
+        Tsl2591::new(sims[0].i2c()).unwrap(),
+        Tsl2591::new(sims[1].i2c()).unwrap(),
+        Tsl2591::new(sims[2].i2c()).unwrap(),
+    ];
+    sims[0].set_light(10.0, 2.0);
+    sims[1].set_light(1.0, 0.2);
+    sims[2].set_light(1000.0, 10.0);
+    for sim in &sims {
+        sim.advance_ms(100);
+    }
+
+    let fused = Fusion::new(FusionStrategy::Max).sample(&mut sensors, true);
+    assert_eq!(fused.lux.unwrap().integer, 2611);
+    assert_eq!(fused.used, 2);
+    assert_eq!(fused.health[2], SensorHealth::Saturated);
+
+    let mut sensors = [block_on(Tsl2591Async::new(sims[0].i2c())).unwrap()];
+    sims[0].advance_ms(100);
+    let fused = block_on(Fusion::new(FusionStrategy::Mean).sample_async(&mut sensors, true));
+    assert_eq!(fused.lux.unwrap().integer, 2611);
+}
+
+#[test]
+fn combines_array_readings() {
+    let sim = SimMux::new(&[0, 1]);
+    let mux = Tca9548a::new(sim.i2c());
+    let mut array = Tsl2591Array::new(&mux, [0, 1]).unwrap();
+    sim.sensor(0).set_light(10.0, 2.0);
+    sim.sensor(1).set_light(1.0, 0.2);
+    sim.sensor(0).advance_ms(100);
+    sim.sensor(1).advance_ms(100);
+
+    let fused = Fusion::weighted([1, 9]).combine(array.read_all(true));
+    assert_eq!(fused.lux.unwrap().integer, 496);
+}
@@ -0,0 +1,51 @@
+use tsl2591_rs::*;
+
+fn classify(ch0: u16, ch1: u16) -> Classification {
+    LightSourceClassifier::default().classify(AlsReading::new(ch0, ch1))
+}
+
+#[test]
+fn classifies_by_ir_ratio() {
+    assert_eq!(
+        classify(1000, 0),
+        Classification {
+            source: LightSource::Led,
+            confidence: 100,
+        }
+    );
+    assert_eq!(classify(1000, 30).confidence, 75);
+    assert_eq!(classify(1000, 100).source, LightSource::Fluorescent);
+    assert_eq!(
+        classify(1000, 275),
+        Classification {
+            source: LightSource::Daylight,
+            confidence: 100,
+        }
+    );
+    assert_eq!(classify(1000, 395).confidence, 52);
+    assert_eq!(
+        classify(1000, 800),
+        Classification {
+            source: LightSource::Incandescent,
+            confidence: 100,
+        }
+    );
+    assert_eq!(classify(1000, 450).confidence, 60);
+    assert_eq!(classify(50, 0).source, LightSource::Unknown);
+
+    assert!(LightSource::Incandescent.is_artificial());
+    assert!(!LightSource::Daylight.is_artificial());
+}
+
+#[test]
+fn thresholds_are_tunable() {
+    // e.g. for a sensor behind IR-absorbing glass
+    let classifier = LightSourceClassifier {
+        daylight_max: 250,
+        ..Default::default()
+    };
+    assert_eq!(
+        classifier.classify(AlsReading::new(1000, 275)).source,
+        LightSource::Incandescent
+    );
+}
@@ -0,0 +1,84 @@
+use tsl2591_rs::{AlsReading, Dn40Lux, Gain, Integration, Lux, LuxModel};
+
+#[test]
+fn negative_values_keep_sign_in_both_components() {
+    let lux = Lux::from_micro_lux(-1_500_000);
+    assert_eq!((lux.integer, lux.fractional), (-1, -500_000));
+    assert_eq!(lux.micro_lux(), -1_500_000);
+    assert!(lux.is_negative());
+
+    let lux = Lux::from_micro_lux(-250_000);
+    assert_eq!((lux.integer, lux.fractional), (0, -250_000));
+    assert_eq!(format!("{:.2}", lux), "-0.25");
+}
+
+#[test]
+fn ordering_and_arithmetic_use_the_combined_value() {
+    let a = Lux::from_milli_lux(1_250);
+    let b = Lux::from_milli_lux(-750);
+
+    assert!(b < Lux::ZERO && Lux::ZERO < a);
+    assert_eq!(a + b, Lux::from_milli_lux(500));
+    assert_eq!(b - a, Lux::from_milli_lux(-2_000));
+    assert_eq!(-a, Lux::from_milli_lux(-1_250));
+    assert_eq!(a * 4, Lux::from_milli_lux(5_000));
+    assert_eq!(a / 2, Lux::from_micro_lux(625_000));
+    assert_eq!(a.scale(2, 5), Lux::from_milli_lux(500));
+
+    // Pairs that mix signs still mean integer + fractional / 1_000_000
+    let mixed = Lux {
+        integer: 2,
+        fractional: -500_000,
+    };
+    assert_eq!(mixed, Lux::from_milli_lux(1_500));
+    assert_eq!(mixed.max(a), mixed);
+}
+
+#[test]
+fn milli_lux_conversions() {
+    let lux = Lux::from_micro_lux(2_611_200_999);
+    assert_eq!(lux.milli_lux(), 2_611_200);
+    assert_eq!(lux.milli_lux_u32(), 2_611_200);
+    assert_eq!((-lux).milli_lux(), -2_611_200);
+    assert_eq!((-lux).milli_lux_u32(), 0);
+}
+
+#[test]
+fn display_rounds_to_requested_decimals() {
+    let lux = Lux::from_micro_lux(2_611_199_600);
+    assert_eq!(format!("{}", lux), "2611.199600");
+    assert_eq!(format!("{:.3}", lux), "2611.200");
+    assert_eq!(format!("{:.0}", lux), "2611");
+    assert_eq!(format!("{:.1}", Lux::from_micro_lux(-950_000)), "-1.0");
+    assert_eq!(format!("{:.1}", Lux::from_micro_lux(-40_000)), "0.0");
+}
+
+#[test]
+fn out_of_range_values_saturate() {
+    assert_eq!(Lux::from_micro_lux(i64::MAX), Lux::MAX);
+    assert_eq!(Lux::from_micro_lux(i64::MIN), Lux::MIN);
+    assert_eq!(Lux::from_milli_lux(i64::MAX), Lux::MAX);
+
+    let bright = Lux::from_milli_lux(2_000_000_000_000);
+    assert_eq!(bright * 1_000_000, Lux::MAX);
+    assert_eq!(bright * -1_000_000, Lux::MIN);
+    assert_eq!(Lux::MAX + Lux::MAX, Lux::MAX);
+    assert_eq!(Lux::MAX.scale(i64::MAX, 1), Lux::MAX);
+}
+
+#[test]
+fn dn40_saturates_with_large_coefficients() {
+    let model = Dn40Lux {
+        device_factor: u32::MAX,
+        coef_b: 0,
+        coef_c: u32::MAX,
+        coef_d: 0,
+    };
+    let reading = AlsReading::new(u16::MAX, 0);
+
+    assert_eq!(model.lux(reading, Gain::Low, Integration::T100ms), Lux::MAX);
+    assert_eq!(
+        Dn40Lux::TSL2591.lux(reading, Gain::Low, Integration::T100ms),
+        Lux::from_micro_lux(267_382_800_000)
+    );
+}
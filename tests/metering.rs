@@ -0,0 +1,67 @@
+use tsl2591_rs::*;
+
+fn assert_near(actual: i64, expected: i64, tolerance: i64) {
+    assert!(
+        (actual - expected).abs() <= tolerance,
+        "{} is not within {} of {}",
+        actual,
+        tolerance,
+        expected
+    );
+}
+
+#[test]
+fn ev100_from_lux() {
+    let meter = LightMeter::default();
+
+    // 2560 lux * 100 / 250 = 2^10
+    assert_eq!(meter.ev100(Lux::from_milli_lux(2_560_000)), Some(Ev(1000)));
+    assert_eq!(meter.ev100(Lux::from_micro_lux(2_500_000)), Some(Ev(0)));
+    assert_eq!(meter.ev100(Lux::from_micro_lux(625_000)), Some(Ev(-200)));
+    assert_eq!(meter.ev100(Lux::from_milli_lux(3_620_000)), Some(Ev(1050)));
+    assert_eq!(meter.ev100(Lux::ZERO), None);
+    assert_eq!(meter.ev100(Lux::from_milli_lux(-5)), None);
+
+    let meter = LightMeter {
+        calibration_constant: 320,
+    };
+    assert_eq!(meter.ev100(Lux::from_milli_lux(3_276_800)), Some(Ev(1000)));
+
+    let meter = LightMeter {
+        calibration_constant: 0,
+    };
+    assert_eq!(meter.ev100(Lux::from_milli_lux(2_560_000)), None);
+}
+
+#[test]
+fn exposure_triples() {
+    let meter = LightMeter::default();
+
+    // Sunny 16: EV15 at ISO 100 is f/16 at 1/125s (8000µs, nominally 1/128s)
+    let exposure = meter.shutter_for(Ev(1500), 100, 160);
+    assert_eq!((exposure.iso, exposure.aperture), (100, 160));
+    assert_near(exposure.shutter_us as i64, 7812, 2);
+
+    // Each stop of ISO halves the shutter time
+    assert_near(
+        meter.shutter_for(Ev(1000), 400, 40).shutter_us as i64,
+        3906,
+        2,
+    );
+
+    assert_near(
+        meter.aperture_for(Ev(1500), 100, 7812).aperture as i64,
+        160,
+        1,
+    );
+    assert_near(meter.iso_for(Ev(1000), 40, 15_625).iso as i64, 100, 1);
+
+    let suggestions: Vec<Exposure> = meter.suggestions(Ev(1000), 100).collect();
+    assert_eq!(suggestions.len(), 10);
+    assert_eq!(suggestions[0].aperture, 10);
+    assert_near(suggestions[0].shutter_us as i64, 977, 1);
+    assert_near(suggestions[4].shutter_us as i64, 15_625, 2);
+
+    // Very dark scenes saturate rather than wrap
+    assert_eq!(meter.shutter_for(Ev(-3000), 100, 220).shutter_us, u32::MAX);
+}
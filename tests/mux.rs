@@ -0,0 +1,143 @@
+mod common;
+
+use core::future::Future;
+use core::pin::pin;
+use core::task::{Context, Waker};
+
+use common::{block_on, join, reg, SimError, SimMux, WakeFlag, I2C_ADDR};
+use embedded_hal_async::i2c::I2c;
+use tsl2591_rs::mux::{Tca9548a, Tsl2591Array, Tsl2591AsyncArray};
+use tsl2591_rs::*;
+
+#[test]
+fn array_reads_every_sensor_through_the_mux() {
+    let sim = SimMux::new(&[0, 3, 7]);
+    let mux = Tca9548a::new(sim.i2c());
+    let mut array = Tsl2591Array::new(&mux, [0, 3, 7]).unwrap();
+
+    sim.sensor(0).set_light(10.0, 2.0);
+    sim.sensor(3).set_light(1.0, 0.2);
+    sim.sensor(7).set_light(1000.0, 10.0);
+    for channel in [0, 3, 7] {
+        sim.sensor(channel).advance_ms(100);
+    }
+
+    let readings = array.read_all(true);
+    assert_eq!(readings[0].as_ref().unwrap().integer, 2611);
+    assert_eq!(readings[1].as_ref().unwrap().integer, 261);
+    assert!(matches!(readings[2], Err(Error::AdcSaturated(_))));
+    assert_eq!(array.channels(), &[0, 3, 7]);
+
+    // Each sensor was set up on its own channel
+    assert_eq!(sim.sensor(3).reg(reg::ENABLE), 0b0000_0011);
+}
+
+#[test]
+fn channel_is_only_selected_when_it_changes() {
+    let sim = SimMux::new(&[1, 2]);
+    let mux = Tca9548a::new(sim.i2c());
+    let mut array = Tsl2591Array::new(&mux, [1, 2]).unwrap();
+    let selects = sim.selects();
+
+    let sensor = array.sensor(0).unwrap();
+    sensor.get_id().unwrap();
+    sensor.get_status().unwrap();
+    assert_eq!(sim.selects(), selects + 1);
+
+    let readings = array.read_all_raw(false);
+    assert!(readings.iter().all(Result::is_ok));
+    assert_eq!(sim.selects(), selects + 2);
+}
+
+#[test]
+fn array_reports_which_sensor_failed() {
+    let sim = SimMux::new(&[0, 1]);
+    let mux = Tca9548a::new(sim.i2c());
+
+    assert!(matches!(
+        Tsl2591Array::new(&mux, [0, 1, 5]),
+        Err((2, Error::I2cError(SimError::Nack)))
+    ));
+}
+
+#[test]
+fn async_array_reads_every_sensor() {
+    let sim = SimMux::new(&[4, 5]);
+    let mux = Tca9548a::new(sim.i2c());
+    let mut array = block_on(Tsl2591AsyncArray::new(&mux, [4, 5])).unwrap();
+
+    for channel in [4, 5] {
+        sim.sensor(channel).set_light(10.0, 2.0);
+        sim.sensor(channel).advance_ms(100);
+    }
+
+    let readings = block_on(array.read_all(true));
+    for reading in readings {
+        assert_eq!(reading.unwrap().integer, 2611);
+    }
+}
+
+#[test]
+fn async_channels_can_be_used_concurrently() {
+    let sim = SimMux::new(&[2, 6]);
+    let mux = Tca9548a::new(sim.i2c());
+    let mut array = block_on(Tsl2591AsyncArray::new(&mux, [2, 6])).unwrap();
+
+    sim.sensor(2).set_light(10.0, 2.0);
+    sim.sensor(6).set_light(1.0, 0.2);
+    for channel in [2, 6] {
+        sim.sensor(channel).advance_ms(100);
+    }
+
+    // Each transaction waits for the other's select and read to finish
+    let [first, second] = array.sensors();
+    let (first, second) = join(first.get_lux(true), second.get_lux(true));
+    assert_eq!(first.unwrap().integer, 2611);
+    assert_eq!(second.unwrap().integer, 261);
+}
+
+#[test]
+fn async_channel_sleeps_until_the_bus_is_handed_back() {
+    let sim = SimMux::new(&[2, 6]);
+    let mux = Tca9548a::new(sim.i2c());
+    let (mut first, mut second) = (mux.channel(2), mux.channel(6));
+    let (mut first_id, mut second_id) = ([0], [0]);
+
+    {
+        // The first transaction stops part way, still holding the bus
+        let mut first = pin!(first.write_read(I2C_ADDR, &[0xA0 | reg::ID], &mut first_id));
+        let mut noop = Context::from_waker(Waker::noop());
+        assert!(first.as_mut().poll(&mut noop).is_pending());
+
+        let (woken, waker) = WakeFlag::waker();
+        let mut context = Context::from_waker(&waker);
+        let mut second = pin!(second.write_read(I2C_ADDR, &[0xA0 | reg::ID], &mut second_id));
+        assert!(second.as_mut().poll(&mut context).is_pending());
+        assert!(!woken.take());
+
+        while first.as_mut().poll(&mut noop).is_pending() {
+            assert!(!woken.take());
+        }
+        assert!(woken.take());
+
+        while second.as_mut().poll(&mut context).is_pending() {}
+    }
+    assert_eq!((first_id, second_id), ([0x50], [0x50]));
+}
+
+#[test]
+fn array_sensors_share_a_lux_model() {
+    let sim = SimMux::new(&[0, 1]);
+    let mux = Tca9548a::new(sim.i2c());
+    let array = Tsl2591Array::new(&mux, [0, 1]).unwrap();
+    let mut array = array.with_lux_model(Dn40Lux::TSL2591);
+
+    for channel in [0, 1] {
+        sim.sensor(channel).set_light(10.0, 2.0);
+        sim.sensor(channel).advance_ms(100);
+    }
+
+    for reading in array.read_all(true) {
+        assert_eq!(reading.unwrap().integer, 2741);
+    }
+}
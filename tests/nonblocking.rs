@@ -0,0 +1,154 @@
+mod common;
+
+use common::{block_on, reg, Sim, SimI2c};
+use tsl2591_rs::*;
+
+fn setup() -> (Sim, Tsl2591Async<SimI2c>) {
+    let sim = Sim::new();
+    let tsl2591 = block_on(Tsl2591Async::new(sim.i2c())).expect("Failed to init sensor");
+    (sim, tsl2591)
+}
+
+#[test]
+fn new_resets_and_powers_on() {
+    let (sim, tsl2591) = setup();
+
+    assert!(tsl2591.powered_on);
+    assert_eq!(sim.reg(reg::ENABLE), 0b0000_0011);
+}
+
+#[test]
+fn get_lux_requires_complete_cycle() {
+    let (sim, mut tsl2591) = setup();
+    sim.set_light(10.0, 2.0);
+
+    assert!(matches!(
+        block_on(tsl2591.get_lux(true)),
+        Err(Error::CycleIncomplete)
+    ));
+
+    sim.advance_ms(100);
+    let lux = block_on(tsl2591.get_lux(true)).unwrap();
+    assert_eq!((lux.integer, lux.fractional), (2611, 200_000));
+}
+
+#[test]
+fn measure_lux_waits_for_fresh_cycle() {
+    let (sim, mut tsl2591) = setup();
+    let mut delay = sim.delay();
+    sim.set_light(10.0, 2.0);
+
+    let lux = block_on(tsl2591.measure_lux(&mut delay)).unwrap();
+    assert_eq!(lux.integer, 2611);
+}
+
+#[test]
+fn wait_for_threshold_event_clears_only_fired_source() {
+    let (sim, tsl2591) = setup();
+    let mut tsl2591 = tsl2591.with_int_pin(sim.int_pin());
+    block_on(async {
+        tsl2591.set_np_threshold(0, 500).await.unwrap();
+        tsl2591.enable_np_interrupt(true).await.unwrap();
+    });
+
+    sim.set_light(10.0, 2.0);
+    let lux = block_on(tsl2591.wait_for_threshold_event()).unwrap();
+    assert_eq!(lux.integer, 2611);
+
+    // The persist-filtered interrupt fires every cycle by default but isn't enabled
+    let status = block_on(tsl2591.get_status()).unwrap();
+    assert!(status.aint && !status.npintr);
+    assert!(!sim.device().int_asserted());
+}
+
+#[test]
+fn wait_for_new_sample_fires_every_cycle() {
+    let (sim, tsl2591) = setup();
+    let mut tsl2591 = tsl2591.with_int_pin(sim.int_pin());
+    block_on(tsl2591.set_persist(Persist::F60)).unwrap();
+    sim.set_light(10.0, 2.0);
+
+    for _ in 0..3 {
+        let lux = block_on(tsl2591.wait_for_new_sample()).unwrap();
+        assert_eq!(lux.integer, 2611);
+    }
+
+    // The caller's persist filter and interrupt enable are left as they were
+    let config = block_on(tsl2591.get_config()).unwrap();
+    assert_eq!(config.persist, Persist::F60);
+    assert!(!config.interrupt_enabled);
+}
+
+#[test]
+fn wait_for_new_sample_ignores_stale_interrupt() {
+    let (sim, tsl2591) = setup();
+    let mut tsl2591 = tsl2591.with_int_pin(sim.int_pin());
+    block_on(tsl2591.enable_interrupt(true)).unwrap();
+
+    // Latch an interrupt from the old light level before asking for a new sample
+    sim.set_light(10.0, 2.0);
+    sim.advance_ms(300);
+    assert!(sim.device().int_asserted());
+    sim.set_light(1.0, 0.2);
+
+    let lux = block_on(tsl2591.wait_for_new_sample()).unwrap();
+    assert_eq!(lux.integer, 261);
+}
+
+#[test]
+fn wait_for_new_sample_ignores_stale_np_interrupt() {
+    let (sim, tsl2591) = setup();
+    let mut tsl2591 = tsl2591.with_int_pin(sim.int_pin());
+    block_on(async {
+        tsl2591.set_np_threshold(0, 500).await.unwrap();
+        tsl2591.enable_np_interrupt(true).await.unwrap();
+    });
+
+    sim.set_light(10.0, 2.0);
+    sim.advance_ms(300);
+    assert!(sim.device().int_asserted());
+    sim.set_light(1.0, 0.2);
+
+    let lux = block_on(tsl2591.wait_for_new_sample()).unwrap();
+    assert_eq!(lux.integer, 261);
+}
+
+#[test]
+fn with_config_constructs_configured_driver() {
+    let sim = Sim::new();
+    let config = Config::new()
+        .with_integration(Integration::T600ms)
+        .with_np_threshold(0, 500)
+        .with_np_interrupt(true);
+    let mut tsl2591 = block_on(Tsl2591Async::with_config(sim.i2c(), config)).unwrap();
+
+    assert_eq!(block_on(tsl2591.get_config()).unwrap(), config);
+}
+
+#[test]
+fn typestate_driver_tracks_power_state() {
+    let sim = Sim::new();
+    let tsl2591 = block_on(typestate::Tsl2591Async::new(sim.i2c())).unwrap();
+    let mut tsl2591 = tsl2591.with_int_pin(sim.int_pin());
+
+    sim.set_light(10.0, 2.0);
+    let lux = block_on(tsl2591.wait_for_new_sample()).unwrap();
+    assert_eq!(lux.integer, 2611);
+
+    let mut tsl2591 = block_on(tsl2591.power_off()).map_err(|(_, e)| e).unwrap();
+    block_on(tsl2591.set_atime(Integration::T200ms)).unwrap();
+    assert_eq!(sim.reg(reg::ENABLE) & 0b0000_0011, 0);
+    block_on(tsl2591.power_on()).map_err(|(_, e)| e).unwrap();
+}
+
+#[test]
+fn free_returns_bus_and_pin() {
+    let (sim, tsl2591) = setup();
+    let mut tsl2591 = tsl2591.with_int_pin(sim.int_pin());
+    block_on(tsl2591.set_again(Gain::Med)).unwrap();
+
+    let (i2c, int_pin) = tsl2591.free();
+    let tsl2591 = block_on(Tsl2591Async::attach(i2c)).unwrap();
+    let mut tsl2591 = tsl2591.with_int_pin(int_pin);
+    assert_eq!(block_on(tsl2591.get_config()).unwrap().gain, Gain::Med);
+}